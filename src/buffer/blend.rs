@@ -0,0 +1,115 @@
+use std::cmp::Ordering;
+
+use crate::buffer::math::mat4::Mat4;
+use crate::buffer::math::vec3::Vec3;
+use crate::buffer::math::vec4::Vec4;
+
+// How a fragment's color is merged with what the framebuffer holds. Alpha is the fragment's
+// unless the mode says otherwise.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BlendMode {
+    // Overwrites, the default for opaque geometry
+    Replace,
+    // src * a + dst * (1 - a)
+    Alpha,
+    // src + dst * (1 - a), for colors already multiplied by their alpha
+    Premultiplied,
+    // dst + src * a
+    Additive,
+    // src * dst, alpha included
+    Multiply,
+    // Per channel, alpha included
+    Min,
+    Max,
+}
+
+// Colors are normalized RGBA, the result is clamped when it is stored.
+pub fn blend(mode: BlendMode, src: Vec4, dst: Vec4) -> Vec4 {
+    let a = src.w;
+    // Coverage accumulates like one layer of paint over another
+    let over = a + dst.w * (1. - a);
+    match mode {
+        BlendMode::Replace => src,
+        BlendMode::Alpha => (src.xyz() * a + dst.xyz() * (1. - a)).extend(over),
+        BlendMode::Premultiplied => (src.xyz() + dst.xyz() * (1. - a)).extend(over),
+        BlendMode::Additive => (dst.xyz() + src.xyz() * a).extend(over),
+        BlendMode::Multiply => src * dst,
+        BlendMode::Min => src.min(dst),
+        BlendMode::Max => src.max(dst),
+    }
+}
+
+// Orders items farthest from the eye first, the order blended surfaces have to be drawn in.
+// position gives a point of each item, such as the center of its bounds, in the space view
+// maps from.
+pub fn sort_back_to_front<T, F>(items: &mut [T], view: &Mat4, position: F)
+where
+    F: Fn(&T) -> Vec3,
+{
+    let distance = |item: &T| (*view * position(item).extend(1.)).xyz().length_squared();
+    items.sort_by(|a, b| {
+        distance(b)
+            .partial_cmp(&distance(a))
+            .unwrap_or(Ordering::Equal)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::math::matrix::Matrix;
+
+    fn assert_close(a: Vec4, b: Vec4) {
+        assert!((a - b).length() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn modes() {
+        let src = Vec4::new(0.8, 0.4, 0.2, 0.25);
+        let dst = Vec4::new(0.2, 0.6, 1., 0.5);
+        let blended = |mode| blend(mode, src, dst);
+
+        // A quarter of the fragment over half covered paint leaves 0.625 covered
+        assert_close(blended(BlendMode::Replace), src);
+        assert_close(blended(BlendMode::Alpha), Vec4::new(0.35, 0.55, 0.8, 0.625));
+        assert_close(
+            blended(BlendMode::Premultiplied),
+            Vec4::new(0.95, 0.85, 0.95, 0.625),
+        );
+        assert_close(
+            blended(BlendMode::Additive),
+            Vec4::new(0.4, 0.7, 1.05, 0.625),
+        );
+        assert_close(
+            blended(BlendMode::Multiply),
+            Vec4::new(0.16, 0.24, 0.2, 0.125),
+        );
+        assert_close(blended(BlendMode::Min), Vec4::new(0.2, 0.4, 0.2, 0.25));
+        assert_close(blended(BlendMode::Max), Vec4::new(0.8, 0.6, 1., 0.5));
+    }
+
+    #[test]
+    fn opaque_and_clear_fragments() {
+        let src = Vec4::new(0.8, 0.4, 0.2, 1.);
+        let dst = Vec4::new(0.2, 0.6, 1., 1.);
+        assert_close(blend(BlendMode::Alpha, src, dst), src);
+        assert_close(blend(BlendMode::Premultiplied, src, dst), src);
+
+        let clear = Vec4::new(0.8, 0.4, 0.2, 0.);
+        assert_close(blend(BlendMode::Alpha, clear, dst), dst);
+        assert_close(blend(BlendMode::Additive, clear, dst), dst);
+    }
+
+    #[test]
+    fn back_to_front() {
+        let mut items = [-1., -5., 2., -3., 0.5];
+        sort_back_to_front(&mut items, &Mat4::identity(), |z| Vec3::new(0., 0., *z));
+        assert_eq!(items, [-5., -3., 2., -1., 0.5]);
+
+        // Seen from z = -4.5 the order turns around
+        let mut view = Mat4::identity();
+        view[11] = 4.5;
+        sort_back_to_front(&mut items, &view, |z| Vec3::new(0., 0., *z));
+        assert_eq!(items, [2., 0.5, -1., -3., -5.]);
+    }
+}
@@ -0,0 +1,475 @@
+use crate::buffer::clamp;
+use crate::buffer::depth::DepthRange;
+use crate::buffer::math::mat3::Mat3;
+use crate::buffer::math::mat4::Mat4;
+use crate::buffer::math::mat4::ProjectionMatrix;
+use crate::buffer::math::mat4::WorldMatrix;
+use crate::buffer::math::quat::Quat;
+use crate::buffer::math::vec3::Vec3;
+
+const WORLD_UP: Vec3 = Vec3::new(0., 1., 0.);
+
+// forward and up are kept unit length and perpendicular. fov is the full vertical angle in
+// degrees, like Mat4::create_perspective takes it. far may be infinite with a reversed depth
+// range.
+#[derive(Clone, Copy, Debug)]
+pub struct Camera {
+    pub position: Vec3,
+    pub forward: Vec3,
+    pub up: Vec3,
+    pub fov: f32,
+    pub aspect: f32,
+    pub near: f32,
+    pub far: f32,
+    pub depth_range: DepthRange,
+}
+
+impl Camera {
+    // Sits at the origin looking down -z.
+    pub fn new(fov: f32, aspect: f32, near: f32, far: f32) -> Camera {
+        Camera {
+            position: Vec3::zero(),
+            forward: Vec3::new(0., 0., -1.),
+            up: WORLD_UP,
+            fov,
+            aspect,
+            near,
+            far,
+            depth_range: DepthRange::Standard,
+        }
+    }
+
+    pub fn look_at(&mut self, position: Vec3, target: Vec3, up: Vec3) {
+        self.position = position;
+        self.look_along(target - position, up);
+    }
+
+    // Keeps the position and turns towards direction, with up as close to the given one as
+    // possible. Nothing changes if the two are parallel.
+    pub fn look_along(&mut self, direction: Vec3, up: Vec3) {
+        let forward = direction.normalize_or_zero();
+        let right = forward.cross(up).normalize_or_zero();
+        if right.length_squared() == 0. {
+            return;
+        }
+        self.forward = forward;
+        self.up = right.cross(forward);
+    }
+
+    pub fn right(&self) -> Vec3 {
+        self.forward.cross(self.up)
+    }
+
+    // The rotation taking the default orientation (looking down -z, y up) to the current one.
+    pub fn orientation(&self) -> Quat {
+        let r = self.right();
+        let (u, b) = (self.up, -self.forward);
+        Quat::from_mat3(&Mat3([r.x, u.x, b.x, r.y, u.y, b.y, r.z, u.z, b.z]))
+    }
+
+    pub fn set_orientation(&mut self, orientation: Quat) {
+        let q = orientation.normalize();
+        self.forward = q.rotate(Vec3::new(0., 0., -1.));
+        self.up = q.rotate(WORLD_UP);
+    }
+
+    // Turns the camera in place, q is given in world space.
+    pub fn rotate(&mut self, q: Quat) {
+        self.set_orientation(q * self.orientation());
+    }
+
+    pub fn view(&self) -> Mat4 {
+        Mat4::set_lookat(self.position, self.position + self.forward, self.up)
+    }
+
+    pub fn projection(&self) -> Mat4 {
+        match self.depth_range {
+            DepthRange::Standard => {
+                Mat4::create_perspective(self.fov, self.aspect, self.near, self.far)
+            }
+            DepthRange::Reversed => {
+                Mat4::create_perspective_reversed(self.fov, self.aspect, self.near, self.far)
+            }
+        }
+    }
+}
+
+// The part of a frame's input the controllers look at, filled in by the viewer or by hand.
+// mouse_delta is in pixels since the last update with y pointing down the screen, scroll is
+// in wheel steps with positive values zooming in.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct InputState {
+    pub forward: bool,
+    pub backward: bool,
+    pub left: bool,
+    pub right: bool,
+    pub up: bool,
+    pub down: bool,
+    pub dragging: bool,
+    pub mouse_delta: [f32; 2],
+    pub scroll: f32,
+}
+
+pub trait CameraController {
+    // dt is the time since the last update in seconds.
+    fn update(&mut self, camera: &mut Camera, input: &InputState, dt: f32);
+}
+
+fn to_radians(degrees: f32) -> f32 {
+    degrees * std::f32::consts::PI / 180.
+}
+
+// Yaw 0 looks down -z and grows to the right, pitch grows upwards. Both in degrees.
+fn yaw_pitch_direction(yaw: f32, pitch: f32) -> Vec3 {
+    let (yaw, pitch) = (to_radians(yaw), to_radians(pitch));
+    Vec3::new(
+        yaw.sin() * pitch.cos(),
+        pitch.sin(),
+        -yaw.cos() * pitch.cos(),
+    )
+}
+
+fn direction_yaw_pitch(direction: Vec3) -> (f32, f32) {
+    let d = direction.normalize_or_zero();
+    let yaw = f32::atan2(d.x, -d.z).to_degrees();
+    let pitch = clamp(d.y, -1., 1.).asin().to_degrees();
+    (yaw, pitch)
+}
+
+fn zoom(distance: f32, scroll: f32, speed: f32, min: f32, max: f32) -> f32 {
+    clamp(distance * f32::exp(-scroll * speed), min, max)
+}
+
+// Mouse look with WASD-style movement. Walking keeps to the horizontal plane, flying moves
+// along the view direction.
+pub struct FirstPersonController {
+    pub yaw: f32,
+    pub pitch: f32,
+    pub speed: f32,
+    pub sensitivity: f32,
+    pub fly: bool,
+}
+
+impl FirstPersonController {
+    pub fn new(camera: &Camera) -> FirstPersonController {
+        let (yaw, pitch) = direction_yaw_pitch(camera.forward);
+        FirstPersonController {
+            yaw,
+            pitch,
+            speed: 5.,
+            sensitivity: 0.3,
+            fly: true,
+        }
+    }
+}
+
+impl CameraController for FirstPersonController {
+    fn update(&mut self, camera: &mut Camera, input: &InputState, dt: f32) {
+        self.yaw += input.mouse_delta[0] * self.sensitivity;
+        self.pitch = clamp(
+            self.pitch - input.mouse_delta[1] * self.sensitivity,
+            -89.,
+            89.,
+        );
+        camera.look_along(yaw_pitch_direction(self.yaw, self.pitch), WORLD_UP);
+
+        let forward = if self.fly {
+            camera.forward
+        } else {
+            Vec3::new(camera.forward.x, 0., camera.forward.z).normalize_or_zero()
+        };
+        let right = camera.right();
+
+        let mut movement = Vec3::zero();
+        let mut push = |pressed: bool, direction: Vec3| {
+            if pressed {
+                movement += direction;
+            }
+        };
+        push(input.forward, forward);
+        push(input.backward, -forward);
+        push(input.right, right);
+        push(input.left, -right);
+        push(input.up, WORLD_UP);
+        push(input.down, -WORLD_UP);
+
+        camera.position += movement.normalize_or_zero() * (self.speed * dt);
+    }
+}
+
+// Circles a target while dragging, the scroll wheel moves closer or further away. Pitch is
+// limited so the camera never flips over the poles.
+pub struct OrbitController {
+    pub target: Vec3,
+    pub distance: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub sensitivity: f32,
+    pub zoom_speed: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+}
+
+impl OrbitController {
+    pub fn new(camera: &Camera, target: Vec3) -> OrbitController {
+        let offset = target - camera.position;
+        let (yaw, pitch) = direction_yaw_pitch(offset);
+        OrbitController {
+            target,
+            distance: offset.length(),
+            yaw,
+            pitch,
+            sensitivity: 0.3,
+            zoom_speed: 0.1,
+            min_distance: 0.1,
+            max_distance: 1000.,
+        }
+    }
+}
+
+impl CameraController for OrbitController {
+    fn update(&mut self, camera: &mut Camera, input: &InputState, _: f32) {
+        if input.dragging {
+            self.yaw += input.mouse_delta[0] * self.sensitivity;
+            self.pitch = clamp(
+                self.pitch - input.mouse_delta[1] * self.sensitivity,
+                -89.,
+                89.,
+            );
+        }
+        self.distance = zoom(
+            self.distance,
+            input.scroll,
+            self.zoom_speed,
+            self.min_distance,
+            self.max_distance,
+        );
+
+        let forward = yaw_pitch_direction(self.yaw, self.pitch);
+        camera.position = self.target - forward * self.distance;
+        camera.look_along(forward, WORLD_UP);
+    }
+}
+
+// Like orbiting, but dragging rotates around the camera's own axes so there are no poles
+// and the camera can roll over the top of the target.
+pub struct TrackballController {
+    pub target: Vec3,
+    pub sensitivity: f32,
+    pub zoom_speed: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+}
+
+impl TrackballController {
+    pub fn new(target: Vec3) -> TrackballController {
+        TrackballController {
+            target,
+            sensitivity: 0.3,
+            zoom_speed: 0.1,
+            min_distance: 0.1,
+            max_distance: 1000.,
+        }
+    }
+}
+
+impl CameraController for TrackballController {
+    fn update(&mut self, camera: &mut Camera, input: &InputState, _: f32) {
+        let mut offset = camera.position - self.target;
+
+        let [dx, dy] = input.mouse_delta;
+        if input.dragging && (dx != 0. || dy != 0.) {
+            // The scene follows the mouse, so the camera turns the opposite way
+            let axis = camera.up * dx + camera.right() * dy;
+            let angle = -to_radians(f32::sqrt(dx * dx + dy * dy) * self.sensitivity);
+            let q = Quat::from_axis_angle(axis, angle);
+            offset = q.rotate(offset);
+            camera.rotate(q);
+        }
+
+        let distance = zoom(
+            offset.length(),
+            input.scroll,
+            self.zoom_speed,
+            self.min_distance,
+            self.max_distance,
+        );
+        camera.position = self.target + offset.normalize_or_zero() * distance;
+        // Keep looking at the target even if the camera did not start out that way
+        camera.look_along(-offset, camera.up);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    // Eye at (0, 0, 5) looking at the origin.
+    fn camera() -> Camera {
+        let mut camera = Camera::new(45., 1., 0.1, 100.);
+        camera.look_at(Vec3::new(0., 0., 5.), Vec3::zero(), WORLD_UP);
+        camera
+    }
+
+    fn drag(dx: f32, dy: f32) -> InputState {
+        InputState {
+            dragging: true,
+            mouse_delta: [dx, dy],
+            ..InputState::default()
+        }
+    }
+
+    fn scroll(steps: f32) -> InputState {
+        InputState {
+            scroll: steps,
+            ..InputState::default()
+        }
+    }
+
+    // eye + forward * distance lands on target, with up perpendicular to forward.
+    fn assert_view(camera: &Camera, eye: Vec3, target: Vec3, up: Vec3) {
+        assert_close(camera.position, eye);
+        let distance = (target - eye).length();
+        assert_close(camera.position + camera.forward * distance, target);
+        assert_close(camera.up, up);
+    }
+
+    #[test]
+    fn first_person_moves_along_the_view() {
+        let mut camera = camera();
+        let mut controller = FirstPersonController::new(&camera);
+        let input = InputState {
+            forward: true,
+            right: true,
+            ..InputState::default()
+        };
+        controller.update(&mut camera, &input, 1.);
+
+        // Diagonal movement is as fast as straight movement
+        let step = 5. / 2f32.sqrt();
+        let eye = Vec3::new(step, 0., 5. - step);
+        assert_view(&camera, eye, eye + Vec3::new(0., 0., -1.), WORLD_UP);
+    }
+
+    #[test]
+    fn first_person_turns_with_the_mouse() {
+        let mut camera = camera();
+        let mut controller = FirstPersonController::new(&camera);
+        // 300 pixels at 0.3 degrees per pixel
+        controller.update(&mut camera, &drag(300., 0.), 0.);
+        let eye = Vec3::new(0., 0., 5.);
+        assert_view(&camera, eye, eye + Vec3::new(1., 0., 0.), WORLD_UP);
+    }
+
+    #[test]
+    fn first_person_walking_stays_level() {
+        let mut camera = camera();
+        let mut controller = FirstPersonController::new(&camera);
+        controller.fly = false;
+        controller.update(&mut camera, &drag(0., -100.), 0.);
+        let input = InputState {
+            forward: true,
+            ..InputState::default()
+        };
+        controller.update(&mut camera, &input, 1.);
+        assert_close(camera.position, Vec3::new(0., 0., 0.));
+        assert!(camera.forward.y > 0.);
+    }
+
+    #[test]
+    fn first_person_pitch_is_clamped() {
+        let mut camera = camera();
+        let mut controller = FirstPersonController::new(&camera);
+        let limit = to_radians(89.);
+
+        controller.update(&mut camera, &drag(0., -10000.), 0.);
+        assert_eq!(controller.pitch, 89.);
+        assert!((camera.forward.y - limit.sin()).abs() < 1e-5);
+        assert!(camera.up.y > 0.);
+
+        controller.update(&mut camera, &drag(0., 20000.), 0.);
+        assert_eq!(controller.pitch, -89.);
+        assert!((camera.forward.y + limit.sin()).abs() < 1e-5);
+        assert!(camera.up.y > 0.);
+    }
+
+    #[test]
+    fn orbit_circles_the_target() {
+        let mut camera = camera();
+        let mut controller = OrbitController::new(&camera, Vec3::zero());
+        controller.update(&mut camera, &drag(300., 0.), 0.);
+        assert_view(&camera, Vec3::new(-5., 0., 0.), Vec3::zero(), WORLD_UP);
+
+        // Moving the mouse without the button does nothing
+        let input = InputState {
+            mouse_delta: [300., 0.],
+            ..InputState::default()
+        };
+        controller.update(&mut camera, &input, 0.);
+        assert_view(&camera, Vec3::new(-5., 0., 0.), Vec3::zero(), WORLD_UP);
+    }
+
+    #[test]
+    fn orbit_pitch_is_clamped() {
+        let mut camera = camera();
+        let mut controller = OrbitController::new(&camera, Vec3::zero());
+        controller.update(&mut camera, &drag(0., 10000.), 0.);
+        assert_eq!(controller.pitch, -89.);
+
+        // Looking down at the target from just short of the pole
+        let limit = to_radians(89.);
+        let eye = Vec3::new(0., limit.sin(), limit.cos()) * 5.;
+        let up = Vec3::new(0., limit.cos(), -limit.sin());
+        assert_view(&camera, eye, Vec3::zero(), up);
+    }
+
+    #[test]
+    fn orbit_zoom_is_limited() {
+        let mut camera = camera();
+        let target = Vec3::new(1., 2., 3.);
+        let mut controller = OrbitController::new(&camera, target);
+
+        controller.update(&mut camera, &scroll(1.), 0.);
+        let expected = (Vec3::new(0., 0., 5.) - target).length() * (-0.1f32).exp();
+        assert!((controller.distance - expected).abs() < 1e-4);
+
+        controller.update(&mut camera, &scroll(1000.), 0.);
+        assert_eq!(controller.distance, controller.min_distance);
+        assert!(((camera.position - target).length() - 0.1).abs() < 1e-4);
+
+        controller.update(&mut camera, &scroll(-1000.), 0.);
+        assert_eq!(controller.distance, controller.max_distance);
+        assert!(((camera.position - target).length() - 1000.).abs() < 1e-2);
+    }
+
+    #[test]
+    fn trackball_follows_the_mouse() {
+        let mut camera = camera();
+        let mut controller = TrackballController::new(Vec3::zero());
+        controller.update(&mut camera, &drag(300., 0.), 0.);
+        assert_view(&camera, Vec3::new(-5., 0., 0.), Vec3::zero(), WORLD_UP);
+    }
+
+    #[test]
+    fn trackball_rolls_over_the_top() {
+        let mut camera = camera();
+        let mut controller = TrackballController::new(Vec3::zero());
+        // 180 degrees up and over, the camera ends up behind the target upside down
+        controller.update(&mut camera, &drag(0., 600.), 0.);
+        assert_view(&camera, Vec3::new(0., 0., -5.), Vec3::zero(), -WORLD_UP);
+    }
+
+    #[test]
+    fn trackball_zoom_is_limited() {
+        let mut camera = camera();
+        let mut controller = TrackballController::new(Vec3::zero());
+        controller.update(&mut camera, &scroll(1000.), 0.);
+        assert_view(&camera, Vec3::new(0., 0., 0.1), Vec3::zero(), WORLD_UP);
+        controller.update(&mut camera, &scroll(-1000.), 0.);
+        assert_view(&camera, Vec3::new(0., 0., 1000.), Vec3::zero(), WORLD_UP);
+    }
+}
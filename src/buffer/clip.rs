@@ -0,0 +1,82 @@
+use crate::buffer::math::vec4::Vec4;
+use crate::buffer::shader::Varying;
+
+// Clip-space planes as (a, b, c, d) with a vertex inside when a*x + b*y + c*z + d*w >= 0.
+pub const PLANES: [Vec4; 6] = [
+    Vec4::new(1., 0., 0., 1.),  // left:   x >= -w
+    Vec4::new(-1., 0., 0., 1.), // right:  x <= w
+    Vec4::new(0., 1., 0., 1.),  // bottom: y >= -w
+    Vec4::new(0., -1., 0., 1.), // top:    y <= w
+    Vec4::new(0., 0., 1., 1.),  // near:   z >= -w
+    Vec4::new(0., 0., -1., 1.), // far:    z <= w
+];
+
+#[derive(Clone, Copy)]
+pub struct ClipVertex<V> {
+    pub position: Vec4,
+    pub varying: V,
+}
+
+impl<V: Varying> ClipVertex<V> {
+    fn lerp(&self, other: &ClipVertex<V>, t: f32) -> ClipVertex<V> {
+        ClipVertex {
+            position: self.position.lerp(other.position, t),
+            varying: self.varying.lerp(&other.varying, t),
+        }
+    }
+}
+
+fn distance(plane: &Vec4, v: &Vec4) -> f32 {
+    plane.dot(*v)
+}
+
+fn inside_all(v: &Vec4) -> bool {
+    PLANES.iter().all(|plane| distance(plane, v) >= 0.)
+}
+
+// Sutherland-Hodgman against the six frustum planes. Returns an empty polygon when
+// everything is outside.
+pub fn clip_polygon<V: Varying>(polygon: Vec<ClipVertex<V>>) -> Vec<ClipVertex<V>> {
+    if polygon.iter().all(|v| inside_all(&v.position)) {
+        return polygon;
+    }
+
+    let mut output = polygon;
+    for plane in PLANES.iter() {
+        if output.len() < 3 {
+            output.clear();
+            break;
+        }
+
+        let input = output;
+        output = Vec::with_capacity(input.len() + 1);
+
+        for i in 0..input.len() {
+            let current = &input[i];
+            let next = &input[(i + 1) % input.len()];
+            let dc = distance(plane, &current.position);
+            let dn = distance(plane, &next.position);
+
+            if dc >= 0. {
+                output.push(*current);
+            }
+            if (dc >= 0.) != (dn >= 0.) {
+                output.push(current.lerp(next, dc / (dc - dn)));
+            }
+        }
+    }
+
+    if output.len() < 3 {
+        output.clear();
+    }
+    output
+}
+
+// Fan triangulation of a convex polygon, keeping the winding of the input.
+pub fn triangulate<V: Copy>(polygon: &[ClipVertex<V>]) -> Vec<[ClipVertex<V>; 3]> {
+    let mut triangles = Vec::new();
+    for i in 1..polygon.len().saturating_sub(1) {
+        triangles.push([polygon[0], polygon[i], polygon[i + 1]]);
+    }
+    triangles
+}
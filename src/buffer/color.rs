@@ -1,15 +1,132 @@
+// How stored values relate to light. Linear values are proportional to intensity, sRGB values
+// are gamma encoded the way images and displays expect, which spends more of the 8 bits on
+// dark tones. Shading and blending always happen on linear values.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ColorSpace {
+    Linear,
+    Srgb,
+}
+
+impl ColorSpace {
+    // Both take and give values in [0, 1].
+    pub fn to_linear(self, value: f32) -> f32 {
+        match self {
+            ColorSpace::Linear => value,
+            ColorSpace::Srgb => srgb_to_linear(value),
+        }
+    }
+
+    pub fn from_linear(self, value: f32) -> f32 {
+        match self {
+            ColorSpace::Linear => value,
+            ColorSpace::Srgb => linear_to_srgb(value),
+        }
+    }
+}
+
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1. / 2.4) - 0.055
+    }
+}
+
+// r, g and b are sRGB encoded, like colors picked in an image editor. Alpha is linear.
 pub struct Color {
     pub r: u8,
     pub g: u8,
     pub b: u8,
+    pub a: u8,
 }
 
 impl Color {
-    pub fn normalize(&mut self) -> (f32, f32, f32) {
+    // The stored values divided by 255, still sRGB encoded.
+    pub fn normalize(&mut self) -> (f32, f32, f32, f32) {
         (
             self.r as f32 / 255.0,
             self.g as f32 / 255.0,
             self.b as f32 / 255.0,
+            self.a as f32 / 255.0,
         )
     }
+
+    pub fn to_linear(&self) -> (f32, f32, f32, f32) {
+        let decode = |c: u8| srgb_to_linear(c as f32 / 255.0);
+        (
+            decode(self.r),
+            decode(self.g),
+            decode(self.b),
+            self.a as f32 / 255.0,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn known_values() {
+        assert_eq!(srgb_to_linear(0.), 0.);
+        assert_close(srgb_to_linear(1.), 1.);
+        assert_close(srgb_to_linear(0.5), 0.21404);
+        assert_close(linear_to_srgb(0.5), 0.73536);
+        assert_close(linear_to_srgb(0.18), 0.46135);
+    }
+
+    #[test]
+    fn round_trips() {
+        for i in 0..=1000 {
+            let v = i as f32 / 1000.;
+            assert_close(linear_to_srgb(srgb_to_linear(v)), v);
+            assert_close(srgb_to_linear(linear_to_srgb(v)), v);
+            for space in [ColorSpace::Linear, ColorSpace::Srgb] {
+                assert_close(space.from_linear(space.to_linear(v)), v);
+            }
+        }
+        assert_eq!(ColorSpace::Linear.to_linear(0.3), 0.3);
+    }
+
+    #[test]
+    fn breakpoint() {
+        // The linear segment and the curve meet without a jump
+        let (srgb, linear) = (0.04045, 0.0031308);
+        assert_close(srgb_to_linear(srgb), linear);
+        assert_close(linear_to_srgb(linear), srgb);
+        for step in [1e-4, -1e-4] {
+            assert!((srgb_to_linear(srgb + step) - linear).abs() < 2e-4);
+            assert!((linear_to_srgb(linear + step / 12.92) - srgb).abs() < 2e-4);
+        }
+        // Straight below it
+        assert_close(srgb_to_linear(0.02), 0.02 / 12.92);
+        assert_close(linear_to_srgb(0.001), 0.01292);
+    }
+
+    #[test]
+    fn colors_decode_all_but_alpha() {
+        let color = Color {
+            r: 255,
+            g: 128,
+            b: 0,
+            a: 128,
+        };
+        let (r, g, b, a) = color.to_linear();
+        assert_close(r, 1.);
+        assert_close(g, srgb_to_linear(128. / 255.));
+        assert_eq!(b, 0.);
+        assert_close(a, 128. / 255.);
+    }
 }
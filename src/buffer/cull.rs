@@ -0,0 +1,230 @@
+use crate::buffer::clip;
+use crate::buffer::math::mat4::Mat4;
+use crate::buffer::math::vec3::Vec3;
+use crate::buffer::math::vec4::Vec4;
+
+// Which faces are dropped before clipping.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CullMode {
+    None,
+    Back,
+    Front,
+}
+
+// Winding of front faces as seen on screen. The meshes here are wound clockwise.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FrontFace {
+    Clockwise,
+    CounterClockwise,
+}
+
+// Counted from the last reset_stats on, in triangles unless the name says otherwise.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct CullStats {
+    // Everything handed to the buffer, culled or not
+    pub triangles: usize,
+    pub meshes_culled: usize,
+    // Triangles of the meshes that were culled as a whole
+    pub frustum_culled: usize,
+    pub face_culled: usize,
+    // Entirely outside the view volume, dropped by the clipper
+    pub clipped: usize,
+}
+
+impl CullStats {
+    pub fn culled(&self) -> usize {
+        self.frustum_culled + self.face_culled + self.clipped
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    // None for no points at all.
+    pub fn from_points<I: IntoIterator<Item = Vec3>>(points: I) -> Option<Aabb> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        let mut bounds = Aabb {
+            min: first,
+            max: first,
+        };
+        for p in points {
+            bounds.min = bounds.min.min(p);
+            bounds.max = bounds.max.max(p);
+        }
+        Some(bounds)
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    // Of the bounding sphere around the center.
+    pub fn radius(&self) -> f32 {
+        (self.max - self.min).length() * 0.5
+    }
+
+    pub fn corners(&self) -> [Vec3; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Vec3::new(a.x, a.y, a.z),
+            Vec3::new(b.x, a.y, a.z),
+            Vec3::new(a.x, b.y, a.z),
+            Vec3::new(b.x, b.y, a.z),
+            Vec3::new(a.x, a.y, b.z),
+            Vec3::new(b.x, a.y, b.z),
+            Vec3::new(a.x, b.y, b.z),
+            Vec3::new(b.x, b.y, b.z),
+        ]
+    }
+}
+
+// True when no part of the box can end up inside the view volume. The clip planes are pulled
+// back into object space, so the bounding sphere settles most meshes with one dot product per
+// plane and only those it straddles need the corners projected.
+pub fn outside_frustum(bounds: &Aabb, obj2proj: &Mat4) -> bool {
+    let (center, radius) = (bounds.center(), bounds.radius());
+    let mut straddled = Vec::new();
+    for plane in clip::PLANES.iter() {
+        let p = object_plane(plane, obj2proj);
+        let distance = p.xyz().dot(center) + p.w;
+        let reach = radius * p.xyz().length();
+        if distance < -reach {
+            return true;
+        }
+        if distance < reach {
+            straddled.push(plane);
+        }
+    }
+
+    let corners = bounds.corners().map(|c| *obj2proj * c.extend(1.));
+    straddled
+        .iter()
+        .any(|plane| corners.iter().all(|c| plane.dot(*c) < 0.))
+}
+
+// The clip-space plane in object coordinates, p * M as a row vector.
+fn object_plane(plane: &Vec4, m: &Mat4) -> Vec4 {
+    let column =
+        |j: usize| plane.x * m[j] + plane.y * m[4 + j] + plane.z * m[8 + j] + plane.w * m[12 + j];
+    Vec4::new(column(0), column(1), column(2), column(3))
+}
+
+// Positive when the triangle is wound clockwise on screen, twice its screen area times the w of
+// each vertex. Being the x, y, w determinant of the clip-space positions it gives the facing
+// even with vertices behind the eye, where the screen area means nothing.
+pub fn clockwise_area(a: Vec4, b: Vec4, c: Vec4) -> f32 {
+    -(a.x * (b.y * c.w - b.w * c.y) - a.y * (b.x * c.w - b.w * c.x) + a.w * (b.x * c.y - b.y * c.x))
+}
+
+// Whether a triangle with the given clockwise_area is dropped. Edge-on triangles cover no
+// pixels and go whenever culling is on.
+pub fn culls(mode: CullMode, front_face: FrontFace, area: f32) -> bool {
+    let front = match front_face {
+        FrontFace::Clockwise => area > 0.,
+        FrontFace::CounterClockwise => area < 0.,
+    };
+    match mode {
+        CullMode::None => false,
+        CullMode::Back => !front,
+        CullMode::Front => front || area == 0.,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::camera::Camera;
+    use crate::buffer::math::matrix::Matrix;
+
+    fn aabb(min: (f32, f32, f32), max: (f32, f32, f32)) -> Aabb {
+        Aabb {
+            min: Vec3::new(min.0, min.1, min.2),
+            max: Vec3::new(max.0, max.1, max.2),
+        }
+    }
+
+    #[test]
+    fn boxes_against_the_clip_volume() {
+        // Object space is clip space, the volume is the cube from -1 to 1
+        let m = Mat4::identity();
+        assert!(!outside_frustum(
+            &aabb((-0.2, -0.2, -0.2), (0.2, 0.2, 0.2)),
+            &m
+        ));
+        assert!(!outside_frustum(&aabb((-5., -5., -5.), (5., 5., 5.)), &m));
+        assert!(outside_frustum(&aabb((2., -0.5, -0.5), (3., 0.5, 0.5)), &m));
+        assert!(outside_frustum(
+            &aabb((-0.5, -0.5, -3.), (0.5, 0.5, -1.5)),
+            &m
+        ));
+
+        // Straddling a plane
+        assert!(!outside_frustum(
+            &aabb((0.5, -0.5, -0.5), (1.5, 0.5, 0.5)),
+            &m
+        ));
+        assert!(!outside_frustum(
+            &aabb((-0.5, 0.9, 0.9), (0.5, 1.5, 1.5)),
+            &m
+        ));
+
+        // Long and thin, the bounding sphere reaches in but every corner is to the right
+        assert!(outside_frustum(
+            &aabb((1.05, -3., -0.1), (1.2, 3., 0.1)),
+            &m
+        ));
+    }
+
+    #[test]
+    fn boxes_against_a_camera() {
+        let mut camera = Camera::new(60., 1., 0.1, 100.);
+        camera.look_at(Vec3::zero(), Vec3::new(0., 0., -1.), Vec3::new(0., 1., 0.));
+        let m = camera.projection() * camera.view();
+
+        let ahead = aabb((-1., -1., -11.), (1., 1., -9.));
+        let behind = aabb((-1., -1., 9.), (1., 1., 11.));
+        let beyond_far = aabb((-1., -1., -130.), (1., 1., -110.));
+        let off_to_the_side = aabb((20., -1., -11.), (22., 1., -9.));
+        let around_the_eye = aabb((-1., -1., -1.), (1., 1., 1.));
+        assert!(!outside_frustum(&ahead, &m));
+        assert!(outside_frustum(&behind, &m));
+        assert!(outside_frustum(&beyond_far, &m));
+        assert!(outside_frustum(&off_to_the_side, &m));
+        assert!(!outside_frustum(&around_the_eye, &m));
+    }
+
+    #[test]
+    fn clockwise_area_sign() {
+        let p = |x: f32, y: f32, w: f32| Vec4::new(x * w, y * w, 0., w);
+        // Up and then down to the right is clockwise with y pointing up
+        let (a, b, c) = (p(0., 0., 1.), p(0., 1., 1.), p(1., 0., 1.));
+        assert_eq!(clockwise_area(a, b, c), 1.);
+        assert_eq!(clockwise_area(a, c, b), -1.);
+        assert_eq!(clockwise_area(b, c, a), 1.);
+
+        // Scaled by every w, the sign stays
+        let (a, b, c) = (p(0., 0., 2.), p(0., 1., 3.), p(1., 0., 0.5));
+        assert_eq!(clockwise_area(a, b, c), 3.);
+
+        assert_eq!(clockwise_area(a, a, c), 0.);
+    }
+
+    #[test]
+    fn cull_modes() {
+        // Whether a clockwise, counter-clockwise and edge-on triangle is dropped
+        let dropped = |mode, front_face| [1., -1., 0.].map(|area| culls(mode, front_face, area));
+        let (cw, ccw) = (FrontFace::Clockwise, FrontFace::CounterClockwise);
+
+        assert_eq!(dropped(CullMode::None, cw), [false, false, false]);
+        assert_eq!(dropped(CullMode::None, ccw), [false, false, false]);
+        assert_eq!(dropped(CullMode::Back, cw), [false, true, true]);
+        assert_eq!(dropped(CullMode::Back, ccw), [true, false, true]);
+        assert_eq!(dropped(CullMode::Front, cw), [true, false, true]);
+        assert_eq!(dropped(CullMode::Front, ccw), [false, true, true]);
+    }
+}
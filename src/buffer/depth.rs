@@ -0,0 +1,211 @@
+// Decides whether a fragment is kept, given its depth and the one stored for the sample.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DepthCompare {
+    Never,
+    Less,
+    LessEqual,
+    Equal,
+    GreaterEqual,
+    Greater,
+    NotEqual,
+    Always,
+}
+
+impl DepthCompare {
+    pub fn passes(self, depth: f32, stored: f32) -> bool {
+        match self {
+            DepthCompare::Never => false,
+            DepthCompare::Less => depth < stored,
+            DepthCompare::LessEqual => depth <= stored,
+            DepthCompare::Equal => depth == stored,
+            DepthCompare::GreaterEqual => depth >= stored,
+            DepthCompare::Greater => depth > stored,
+            DepthCompare::NotEqual => depth != stored,
+            DepthCompare::Always => true,
+        }
+    }
+}
+
+// Polygon offset, added to every depth of a triangle before the test. Decals drawn over a
+// surface are pulled towards the eye, with a negative bias for Less and a positive one for
+// Greater.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct DepthBias {
+    // In steps of the smallest difference a float resolves at the triangle's largest depth
+    pub constant: f32,
+    // Times the steepest change of depth per pixel, surfaces seen at a grazing angle need more
+    pub slope_scaled: f32,
+}
+
+impl DepthBias {
+    pub fn offset(&self, depths: [f32; 3], slope: f32) -> f32 {
+        if self.constant == 0. && self.slope_scaled == 0. {
+            return 0.;
+        }
+        let max = depths.iter().fold(0f32, |max, z| max.max(z.abs()));
+        // One unit in the last place of max, as Direct3D does for float depth buffers
+        let unit = if max > 0. {
+            (max.log2().floor() - 23.).exp2()
+        } else {
+            0.
+        };
+        self.constant * unit + self.slope_scaled * slope
+    }
+}
+
+// How the projection lays out depth. Standard maps near to -1 and far to 1 and keeps what is
+// closest with Less. Reversed maps near to 1 and far to 0 and keeps it with Greater: floats are
+// densest around 0, which then lands where the perspective divide leaves the least precision,
+// so the two mostly cancel out.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DepthRange {
+    Standard,
+    Reversed,
+}
+
+impl DepthRange {
+    pub fn compare(self) -> DepthCompare {
+        match self {
+            DepthRange::Standard => DepthCompare::Less,
+            DepthRange::Reversed => DepthCompare::Greater,
+        }
+    }
+
+    // What the depth buffer starts out as. Reversed clears to the far plane, so the test drops
+    // what lies beyond it, which the clip planes made for -1..1 let through.
+    pub fn clear_value(self) -> f32 {
+        match self {
+            DepthRange::Standard => f32::INFINITY,
+            DepthRange::Reversed => 0.,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::camera::Camera;
+    use crate::buffer::color::Color;
+    use crate::buffer::light::Lighting;
+    use crate::buffer::light::Material;
+    use crate::buffer::light::ShadingMode;
+    use crate::buffer::math::vec3::Vec3;
+    use crate::buffer::mesh::Mesh;
+    use crate::buffer::mesh::Sphere;
+    use crate::buffer::Buffer;
+
+    #[test]
+    fn compare_functions() {
+        // Which of depth below, equal to and above the stored one pass
+        let table = [
+            (DepthCompare::Never, [false, false, false]),
+            (DepthCompare::Less, [true, false, false]),
+            (DepthCompare::LessEqual, [true, true, false]),
+            (DepthCompare::Equal, [false, true, false]),
+            (DepthCompare::GreaterEqual, [false, true, true]),
+            (DepthCompare::Greater, [false, false, true]),
+            (DepthCompare::NotEqual, [true, false, true]),
+            (DepthCompare::Always, [true, true, true]),
+        ];
+        for (compare, expected) in table.iter() {
+            let passes = [0.25, 0.5, 0.75].map(|depth| compare.passes(depth, 0.5));
+            assert_eq!(passes, *expected, "{:?}", compare);
+        }
+        assert!(DepthCompare::Less.passes(0.5, f32::INFINITY));
+        assert!(DepthCompare::Greater.passes(0.5, 0.));
+    }
+
+    #[test]
+    fn constant_bias_scales_with_depth() {
+        let bias = DepthBias {
+            constant: 2.,
+            slope_scaled: 0.,
+        };
+        assert_eq!(bias.offset([0.5, 0.6, 0.7], 10.), 2. * (-24f32).exp2());
+        // The largest magnitude picks the unit, whatever its sign
+        assert_eq!(bias.offset([0.1, -3., 1.], 10.), 2. * (-22f32).exp2());
+        assert_eq!(bias.offset([0., 0., 0.], 10.), 0.);
+        assert_eq!(DepthBias::default().offset([0.5, 0.6, 0.7], 10.), 0.);
+    }
+
+    #[test]
+    fn slope_scaled_bias() {
+        let slope = DepthBias {
+            constant: 0.,
+            slope_scaled: 1.5,
+        };
+        assert_eq!(slope.offset([0.5, 0.6, 0.7], 0.), 0.);
+        assert_eq!(slope.offset([0.5, 0.6, 0.7], 0.25), 0.375);
+
+        let both = DepthBias {
+            constant: -4.,
+            slope_scaled: -1.,
+        };
+        assert_eq!(
+            both.offset([0.5, 0.6, 0.7], 0.25),
+            -4. * (-24f32).exp2() - 0.25
+        );
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(DepthRange::Standard.compare(), DepthCompare::Less);
+        assert_eq!(DepthRange::Reversed.compare(), DepthCompare::Greater);
+        // Nothing fails against a cleared buffer
+        for range in [DepthRange::Standard, DepthRange::Reversed] {
+            assert!(range.compare().passes(1., range.clear_value()));
+            assert!(range.compare().passes(0.001, range.clear_value()));
+        }
+    }
+
+    // A near sphere drawn first, then a far one it partly hides.
+    fn occlusion(range: DepthRange) -> Buffer {
+        let mut camera = Camera::new(45., 1., 0.1, 100.);
+        camera.depth_range = range;
+        camera.look_at(Vec3::new(0., 0., 5.), Vec3::zero(), Vec3::new(0., 1., 0.));
+        let mut buf = Buffer::new(64, 64, camera.projection(), camera.view());
+        buf.depth_compare = range.compare();
+        buf.clear_color(Color {
+            r: 0,
+            g: 0,
+            b: 0,
+            a: 255,
+        });
+        buf.clear_depth(range.clear_value());
+
+        let mut sphere = Mesh::construct();
+        <Mesh as Sphere>::new(&mut sphere, 24, 16);
+        let lighting = Lighting::new(Vec3::splat(1.));
+        buf.translate(Vec3::new(-0.5, 0., 1.));
+        buf.draw_mesh_lit(
+            &sphere,
+            &lighting,
+            &Material::new(Vec3::new(1., 0., 0.)),
+            ShadingMode::Gouraud,
+        );
+        buf.clear_object_matrices();
+        buf.translate(Vec3::new(0.5, 0., -1.));
+        buf.draw_mesh_lit(
+            &sphere,
+            &lighting,
+            &Material::new(Vec3::new(0., 0., 1.)),
+            ShadingMode::Gouraud,
+        );
+        buf
+    }
+
+    #[test]
+    fn reversed_z_keeps_the_same_surfaces() {
+        let standard = occlusion(DepthRange::Standard);
+        let reversed = occlusion(DepthRange::Reversed);
+
+        // Both spheres show, and the near one covers the middle
+        let middle = standard.data[32 * 64 + 32];
+        assert!(standard
+            .data
+            .iter()
+            .any(|p| *p != middle && *p != standard.data[0]));
+        assert_ne!(middle, standard.data[0]);
+        assert!(standard.data == reversed.data);
+    }
+}
@@ -0,0 +1,682 @@
+use std::fmt;
+
+use gltf::image::Format;
+use gltf::material::AlphaMode;
+use gltf::mesh::Mode;
+use gltf::texture::MagFilter;
+use gltf::texture::MinFilter;
+use gltf::texture::WrappingMode;
+
+use crate::buffer::color::ColorSpace;
+use crate::buffer::light::Material;
+use crate::buffer::math::mat4::Mat4;
+use crate::buffer::math::matrix::Matrix;
+use crate::buffer::math::vec3::Vec3;
+use crate::buffer::math::vec4::Vec4;
+use crate::buffer::mesh::Mesh;
+use crate::buffer::mesh::NormalWeighting;
+use crate::buffer::mesh::Vertex;
+use crate::buffer::texture::Filter;
+use crate::buffer::texture::MipFilter;
+use crate::buffer::texture::Sampler;
+use crate::buffer::texture::Texture;
+use crate::buffer::texture::Wrap;
+
+#[derive(Debug)]
+pub enum GltfError {
+    Import(gltf::Error),
+    MissingPositions {
+        mesh: usize,
+        primitive: usize,
+    },
+    // An attribute with a different number of elements than POSITION
+    AttributeCount {
+        mesh: usize,
+        primitive: usize,
+        attribute: &'static str,
+        expected: usize,
+        found: usize,
+    },
+    // An index past the end of the vertex attributes
+    IndexOutOfRange {
+        mesh: usize,
+        primitive: usize,
+        index: u32,
+        vertices: usize,
+    },
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GltfError::Import(e) => write!(f, "{}", e),
+            GltfError::MissingPositions { mesh, primitive } => write!(
+                f,
+                "primitive {} of mesh {} has no POSITION attribute",
+                primitive, mesh
+            ),
+            GltfError::AttributeCount {
+                mesh,
+                primitive,
+                attribute,
+                expected,
+                found,
+            } => write!(
+                f,
+                "primitive {} of mesh {} has {} {} values for {} positions",
+                primitive, mesh, found, attribute, expected
+            ),
+            GltfError::IndexOutOfRange {
+                mesh,
+                primitive,
+                index,
+                vertices,
+            } => write!(
+                f,
+                "primitive {} of mesh {} uses index {} of {} vertices",
+                primitive, mesh, index, vertices
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GltfError {}
+
+impl From<gltf::Error> for GltfError {
+    fn from(e: gltf::Error) -> GltfError {
+        GltfError::Import(e)
+    }
+}
+
+pub struct GltfTexture {
+    pub image: usize,
+    pub sampler: Sampler,
+}
+
+// Metallic-roughness material, texture fields index GltfScene::textures.
+#[derive(Clone, Debug)]
+pub struct PbrMaterial {
+    pub name: Option<String>,
+    pub base_color: Vec4,
+    pub base_color_texture: Option<usize>,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub metallic_roughness_texture: Option<usize>,
+    pub normal_texture: Option<usize>,
+    pub emissive: Vec3,
+    pub double_sided: bool,
+    // Whether the alpha of base_color is meant for blending, otherwise it is ignored
+    pub alpha_blend: bool,
+}
+
+impl PbrMaterial {
+    // Rough Blinn-Phong equivalent for use with the lighting module.
+    pub fn material(&self) -> Material {
+        let base = self.base_color.xyz();
+        let dielectric = 0.04 * (1. - self.metallic);
+        let r4 = f32::max(self.roughness.powi(4), 1e-4);
+
+        Material {
+            ambient: base,
+            diffuse: base * (1. - self.metallic),
+            specular: Vec3::splat(dielectric) + base * self.metallic,
+            shininess: f32::max(2. / r4 - 2., 1.),
+            alpha: if self.alpha_blend {
+                self.base_color.w
+            } else {
+                1.
+            },
+        }
+    }
+}
+
+pub struct GltfPrimitive {
+    pub mesh: Mesh,
+    pub material: Option<usize>,
+}
+
+pub struct GltfMesh {
+    pub name: Option<String>,
+    pub primitives: Vec<GltfPrimitive>,
+}
+
+// local is the node's own transform, global includes all of its parents. Both can be handed
+// to Buffer::transform before drawing the node's mesh.
+pub struct GltfNode {
+    pub name: Option<String>,
+    pub mesh: Option<usize>,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    pub local: Mat4,
+    pub global: Mat4,
+}
+
+pub struct GltfScene {
+    pub meshes: Vec<GltfMesh>,
+    pub materials: Vec<PbrMaterial>,
+    pub textures: Vec<GltfTexture>,
+    pub images: Vec<Texture>,
+    pub nodes: Vec<GltfNode>,
+    pub roots: Vec<usize>,
+}
+
+impl GltfScene {
+    // Nodes reachable from the roots that carry a mesh, with their global transforms. A mesh
+    // shows up once for every node using it.
+    pub fn instances(&self) -> Vec<(Mat4, &GltfMesh)> {
+        let mut instances = Vec::new();
+        let mut stack: Vec<usize> = self.roots.iter().rev().copied().collect();
+        while let Some(i) = stack.pop() {
+            let node = &self.nodes[i];
+            if let Some(mesh) = node.mesh {
+                instances.push((node.global, &self.meshes[mesh]));
+            }
+            stack.extend(node.children.iter().rev());
+        }
+        instances
+    }
+}
+
+// glTF matrices are column-major, Mat4 is row-major.
+fn to_mat4(m: [[f32; 4]; 4]) -> Mat4 {
+    let mut result: Mat4 = Mat4::zeros();
+    for (column, values) in m.iter().enumerate() {
+        for (row, value) in values.iter().enumerate() {
+            result[row * 4 + column] = *value;
+        }
+    }
+    result
+}
+
+// Float images are always linear, space only applies to the integer formats.
+fn to_texture(data: &gltf::image::Data, space: ColorSpace) -> Texture {
+    let (channels, size) = match data.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+
+    let read = |offset: usize| -> f32 {
+        let bytes = &data.pixels[offset..offset + size];
+        match size {
+            1 => space.to_linear(bytes[0] as f32 / 255.),
+            2 => space.to_linear(u16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 65535.),
+            _ => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        }
+    };
+
+    let texels = (data.width * data.height) as usize;
+    let mut pixels = Vec::with_capacity(texels);
+    for i in 0..texels {
+        let base = i * channels * size;
+        let r = read(base);
+        // Grey images are spread over all three channels
+        let (g, b) = if channels >= 3 {
+            (read(base + size), read(base + 2 * size))
+        } else {
+            (r, r)
+        };
+        pixels.push([r, g, b]);
+    }
+
+    let mut texture = Texture::new(data.width, data.height, pixels);
+    texture.generate_mipmaps();
+    texture
+}
+
+fn to_sampler(sampler: &gltf::texture::Sampler) -> Sampler {
+    let filter = match sampler.mag_filter() {
+        Some(MagFilter::Nearest) => Filter::Nearest,
+        _ => Filter::Bilinear,
+    };
+    let mip_filter = match sampler.min_filter() {
+        Some(MinFilter::Nearest) | Some(MinFilter::Linear) => MipFilter::None,
+        Some(MinFilter::NearestMipmapNearest) | Some(MinFilter::LinearMipmapNearest) => {
+            MipFilter::Nearest
+        }
+        _ => MipFilter::Linear,
+    };
+    // Sampler has a single addressing mode, so wrap_t follows wrap_s
+    let wrap = match sampler.wrap_s() {
+        WrappingMode::ClampToEdge => Wrap::Clamp,
+        WrappingMode::MirroredRepeat => Wrap::Mirror,
+        WrappingMode::Repeat => Wrap::Repeat,
+    };
+
+    let mut result = Sampler::new(filter, wrap);
+    result.mip_filter = mip_filter;
+    result
+}
+
+fn to_mesh(
+    primitive: &gltf::Primitive,
+    buffers: &[gltf::buffer::Data],
+    mesh_index: usize,
+) -> Result<Option<Mesh>, GltfError> {
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+
+    let positions: Vec<[f32; 3]> = match reader.read_positions() {
+        Some(positions) => positions.collect(),
+        None => {
+            return Err(GltfError::MissingPositions {
+                mesh: mesh_index,
+                primitive: primitive.index(),
+            })
+        }
+    };
+    let normals: Option<Vec<[f32; 3]>> = reader.read_normals().map(|n| n.collect());
+    let uvs: Option<Vec<[f32; 2]>> = reader.read_tex_coords(0).map(|t| t.into_f32().collect());
+
+    let counts = [
+        ("NORMAL", normals.as_ref().map(|n| n.len())),
+        ("TEXCOORD_0", uvs.as_ref().map(|t| t.len())),
+    ];
+    for (attribute, count) in counts.iter() {
+        match *count {
+            Some(found) if found != positions.len() => {
+                return Err(GltfError::AttributeCount {
+                    mesh: mesh_index,
+                    primitive: primitive.index(),
+                    attribute,
+                    expected: positions.len(),
+                    found,
+                })
+            }
+            _ => {}
+        }
+    }
+
+    let order: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..positions.len() as u32).collect(),
+    };
+
+    let mut triangles: Vec<[u32; 3]> = Vec::new();
+    match primitive.mode() {
+        Mode::Triangles => {
+            for t in order.chunks_exact(3) {
+                triangles.push([t[0], t[1], t[2]]);
+            }
+        }
+        Mode::TriangleStrip => {
+            for i in 2..order.len() {
+                if i % 2 == 0 {
+                    triangles.push([order[i - 2], order[i - 1], order[i]]);
+                } else {
+                    triangles.push([order[i - 1], order[i - 2], order[i]]);
+                }
+            }
+        }
+        Mode::TriangleFan => {
+            for i in 2..order.len() {
+                triangles.push([order[0], order[i - 1], order[i]]);
+            }
+        }
+        // Points and lines cannot be drawn by the rasterizer
+        _ => return Ok(None),
+    }
+
+    let out_of_range = triangles
+        .iter()
+        .flatten()
+        .find(|i| **i as usize >= positions.len());
+    if let Some(index) = out_of_range {
+        return Err(GltfError::IndexOutOfRange {
+            mesh: mesh_index,
+            primitive: primitive.index(),
+            index: *index,
+            vertices: positions.len(),
+        });
+    }
+
+    let mut mesh = Mesh::construct();
+    for (i, position) in positions.iter().enumerate() {
+        mesh.vertices.push(Vertex {
+            position: Vec3::from(*position),
+            normal: normals.as_ref().map(|n| n[i].into()).unwrap_or_default(),
+            uv: uvs.as_ref().map(|t| t[i].into()).unwrap_or_default(),
+        });
+    }
+    // glTF front faces are counter-clockwise, meshes here are wound clockwise
+    for [a, b, c] in triangles {
+        mesh.indices.push([a, c, b]);
+    }
+    mesh.v_size = mesh.vertices.len() as u32;
+    mesh.t_size = mesh.indices.len() as u32;
+
+    if normals.is_none() {
+        mesh.compute_smooth_normals(NormalWeighting::Angle);
+    }
+    Ok(Some(mesh))
+}
+
+fn to_material(material: &gltf::Material) -> PbrMaterial {
+    let pbr = material.pbr_metallic_roughness();
+    PbrMaterial {
+        name: material.name().map(|s| s.to_string()),
+        base_color: pbr.base_color_factor().into(),
+        base_color_texture: pbr.base_color_texture().map(|t| t.texture().index()),
+        metallic: pbr.metallic_factor(),
+        roughness: pbr.roughness_factor(),
+        metallic_roughness_texture: pbr
+            .metallic_roughness_texture()
+            .map(|t| t.texture().index()),
+        normal_texture: material.normal_texture().map(|t| t.texture().index()),
+        emissive: material.emissive_factor().into(),
+        double_sided: material.double_sided(),
+        alpha_blend: material.alpha_mode() == AlphaMode::Blend,
+    }
+}
+
+// Reads a .gltf (with its .bin and image files) or a .glb from disk. External files are
+// resolved relative to the given path, nothing is fetched over the network.
+pub fn load_gltf(path: &str) -> Result<GltfScene, GltfError> {
+    let (document, buffers, images) = gltf::import(path)?;
+    to_scene(document, buffers, images)
+}
+
+// Reads a .glb, or a .gltf whose buffers and images are embedded as data URIs.
+pub fn parse_gltf(data: &[u8]) -> Result<GltfScene, GltfError> {
+    let (document, buffers, images) = gltf::import_slice(data)?;
+    to_scene(document, buffers, images)
+}
+
+fn to_scene(
+    document: gltf::Document,
+    buffers: Vec<gltf::buffer::Data>,
+    images: Vec<gltf::image::Data>,
+) -> Result<GltfScene, GltfError> {
+    let mut meshes = Vec::new();
+    for mesh in document.meshes() {
+        let mut primitives = Vec::new();
+        for primitive in mesh.primitives() {
+            if let Some(converted) = to_mesh(&primitive, &buffers, mesh.index())? {
+                primitives.push(GltfPrimitive {
+                    mesh: converted,
+                    material: primitive.material().index(),
+                });
+            }
+        }
+        meshes.push(GltfMesh {
+            name: mesh.name().map(|s| s.to_string()),
+            primitives,
+        });
+    }
+
+    let materials = document.materials().map(|m| to_material(&m)).collect();
+
+    let textures = document
+        .textures()
+        .map(|t| GltfTexture {
+            image: t.source().index(),
+            sampler: to_sampler(&t.sampler()),
+        })
+        .collect();
+
+    // Base color and emissive images hold sRGB colors, the others linear data
+    let mut spaces = vec![ColorSpace::Linear; images.len()];
+    for material in document.materials() {
+        let pbr = material.pbr_metallic_roughness();
+        let base_color = pbr.base_color_texture().map(|t| t.texture());
+        let emissive = material.emissive_texture().map(|t| t.texture());
+        let textures = [base_color, emissive];
+        for texture in textures.iter().flatten() {
+            spaces[texture.source().index()] = ColorSpace::Srgb;
+        }
+    }
+    let images = images
+        .iter()
+        .zip(spaces)
+        .map(|(data, space)| to_texture(data, space))
+        .collect();
+
+    let mut nodes: Vec<GltfNode> = document
+        .nodes()
+        .map(|node| GltfNode {
+            name: node.name().map(|s| s.to_string()),
+            mesh: node.mesh().map(|m| m.index()),
+            parent: None,
+            children: node.children().map(|c| c.index()).collect(),
+            local: to_mat4(node.transform().matrix()),
+            global: Mat4::identity(),
+        })
+        .collect();
+
+    for i in 0..nodes.len() {
+        for c in nodes[i].children.clone() {
+            nodes[c].parent = Some(i);
+        }
+    }
+
+    let roots: Vec<usize> = match document.default_scene() {
+        Some(scene) => scene.nodes().map(|n| n.index()).collect(),
+        None => (0..nodes.len())
+            .filter(|i| nodes[*i].parent.is_none())
+            .collect(),
+    };
+
+    // Parents are resolved before their children
+    let mut stack: Vec<(usize, Mat4)> = roots.iter().map(|r| (*r, Mat4::identity())).collect();
+    while let Some((i, parent)) = stack.pop() {
+        let global = parent * nodes[i].local;
+        nodes[i].global = global;
+        for c in nodes[i].children.iter() {
+            stack.push((*c, global));
+        }
+    }
+
+    Ok(GltfScene {
+        meshes,
+        materials,
+        textures,
+        images,
+        nodes,
+        roots,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::math::vec2::Vec2;
+
+    // A .glb holding a single primitive. Attributes are flat float arrays, TEXCOORD_0 has
+    // two components and everything else three.
+    fn glb(attributes: &[(&str, &[f32])], indices: Option<&[u16]>, mode: u32) -> Vec<u8> {
+        let mut bin: Vec<u8> = Vec::new();
+        let mut views = Vec::new();
+        let mut accessors = Vec::new();
+        let mut names = Vec::new();
+
+        for (i, (name, values)) in attributes.iter().enumerate() {
+            let (kind, components) = if *name == "TEXCOORD_0" {
+                ("VEC2", 2)
+            } else {
+                ("VEC3", 3)
+            };
+            let count = values.len() / components;
+            let bounds = |pick: fn(f32, f32) -> f32| {
+                let bound: Vec<String> = (0..components)
+                    .map(|c| {
+                        let column = values.iter().skip(c).step_by(components);
+                        column.copied().fold(values[c], pick).to_string()
+                    })
+                    .collect();
+                bound.join(",")
+            };
+            views.push(format!(
+                r#"{{"buffer":0,"byteOffset":{},"byteLength":{}}}"#,
+                bin.len(),
+                4 * values.len()
+            ));
+            accessors.push(format!(
+                concat!(
+                    r#"{{"bufferView":{},"componentType":5126,"count":{},"type":"{}","#,
+                    r#""min":[{}],"max":[{}]}}"#
+                ),
+                i,
+                count,
+                kind,
+                bounds(f32::min),
+                bounds(f32::max)
+            ));
+            names.push(format!(r#""{}":{}"#, name, i));
+            for value in values.iter() {
+                bin.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+
+        let mut primitive = format!(r#""attributes":{{{}}},"mode":{}"#, names.join(","), mode);
+        if let Some(indices) = indices {
+            views.push(format!(
+                r#"{{"buffer":0,"byteOffset":{},"byteLength":{}}}"#,
+                bin.len(),
+                2 * indices.len()
+            ));
+            accessors.push(format!(
+                r#"{{"bufferView":{},"componentType":5123,"count":{},"type":"SCALAR"}}"#,
+                views.len() - 1,
+                indices.len()
+            ));
+            primitive += &format!(r#","indices":{}"#, accessors.len() - 1);
+            for index in indices.iter() {
+                bin.extend_from_slice(&index.to_le_bytes());
+            }
+        }
+        bin.resize(bin.len().next_multiple_of(4), 0);
+
+        let mut json = format!(
+            concat!(
+                r#"{{"asset":{{"version":"2.0"}},"buffers":[{{"byteLength":{}}}],"#,
+                r#""bufferViews":[{}],"accessors":[{}],"meshes":[{{"primitives":[{{{}}}]}}],"#,
+                r#""nodes":[{{"mesh":0}}],"scenes":[{{"nodes":[0]}}],"scene":0}}"#
+            ),
+            bin.len(),
+            views.join(","),
+            accessors.join(","),
+            primitive
+        )
+        .into_bytes();
+        json.resize(json.len().next_multiple_of(4), b' ');
+
+        let mut glb = Vec::new();
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&(12 + 8 + json.len() as u32 + 8 + bin.len() as u32).to_le_bytes());
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"JSON");
+        glb.extend_from_slice(&json);
+        glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"BIN\0");
+        glb.extend_from_slice(&bin);
+        glb
+    }
+
+    const TRIANGLES: u32 = 4;
+    const TRIANGLE_STRIP: u32 = 5;
+    const TRIANGLE_FAN: u32 = 6;
+
+    // A unit square in the xy plane, counter-clockwise seen from +z.
+    const SQUARE: [f32; 12] = [0., 0., 0., 1., 0., 0., 1., 1., 0., 0., 1., 0.];
+
+    fn first_mesh(data: &[u8]) -> Mesh {
+        let scene = parse_gltf(data).unwrap();
+        assert_eq!(scene.meshes[0].primitives.len(), 1);
+        scene.meshes[0].primitives[0].mesh.clone()
+    }
+
+    #[test]
+    fn triangles_are_wound_clockwise() {
+        let mesh = first_mesh(&glb(
+            &[("POSITION", &SQUARE)],
+            Some(&[0, 1, 2, 0, 2, 3]),
+            TRIANGLES,
+        ));
+        assert_eq!(mesh.indices, [[0, 2, 1], [0, 3, 2]]);
+        assert_eq!(mesh.t_size, 2);
+    }
+
+    #[test]
+    fn strips_and_fans() {
+        // The strip runs 0 1 3 2 around the square, every second triangle is flipped back
+        let strip = first_mesh(&glb(
+            &[("POSITION", &SQUARE)],
+            Some(&[0, 1, 3, 2]),
+            TRIANGLE_STRIP,
+        ));
+        assert_eq!(strip.indices, [[0, 3, 1], [3, 2, 1]]);
+
+        let fan = first_mesh(&glb(&[("POSITION", &SQUARE)], None, TRIANGLE_FAN));
+        assert_eq!(fan.indices, [[0, 2, 1], [0, 3, 2]]);
+    }
+
+    #[test]
+    fn missing_normals_and_uvs() {
+        let mesh = first_mesh(&glb(&[("POSITION", &SQUARE)], None, TRIANGLE_FAN));
+        for v in mesh.vertices.iter() {
+            assert!((v.normal - Vec3::new(0., 0., 1.)).length() < 1e-5);
+            assert_eq!(v.uv, Vec2::zero());
+        }
+
+        let normals = [0., 1., 0., 0., 1., 0., 0., 1., 0., 0., 1., 0.];
+        let uvs = [0., 0., 1., 0., 1., 1., 0., 1.];
+        let attributes = [
+            ("POSITION", &SQUARE[..]),
+            ("NORMAL", &normals[..]),
+            ("TEXCOORD_0", &uvs[..]),
+        ];
+        let mesh = first_mesh(&glb(&attributes, None, TRIANGLE_FAN));
+        // Given normals are kept even when they do not match the faces
+        assert!(mesh
+            .vertices
+            .iter()
+            .all(|v| v.normal == Vec3::new(0., 1., 0.)));
+        assert_eq!(mesh.vertices[2].uv, Vec2::new(1., 1.));
+    }
+
+    #[test]
+    fn points_and_lines_are_skipped() {
+        for mode in [0, 1, 2, 3] {
+            let scene = parse_gltf(&glb(&[("POSITION", &SQUARE)], None, mode)).unwrap();
+            assert!(scene.meshes[0].primitives.is_empty());
+        }
+    }
+
+    #[test]
+    fn attribute_count_mismatch() {
+        let normals = [0., 0., 1., 0., 0., 1.];
+        let attributes = [("POSITION", &SQUARE[..]), ("NORMAL", &normals[..])];
+        match parse_gltf(&glb(&attributes, None, TRIANGLE_FAN)) {
+            Err(GltfError::AttributeCount {
+                attribute,
+                expected,
+                found,
+                ..
+            }) => {
+                assert_eq!(attribute, "NORMAL");
+                assert_eq!((expected, found), (4, 2));
+            }
+            _ => panic!("short NORMAL was accepted"),
+        }
+    }
+
+    #[test]
+    fn index_out_of_range() {
+        for mode in [TRIANGLES, TRIANGLE_STRIP, TRIANGLE_FAN] {
+            let data = glb(&[("POSITION", &SQUARE)], Some(&[0, 1, 4]), mode);
+            match parse_gltf(&data) {
+                Err(GltfError::IndexOutOfRange {
+                    index, vertices, ..
+                }) => assert_eq!((index, vertices), (4, 4)),
+                _ => panic!("index 4 of 4 vertices was accepted"),
+            }
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use std::io;
+use std::io::Write;
+
+use crate::buffer::math::vec4::Vec4;
+
+// Writers for linear float images, rows given top to bottom. Neither needs the image crate.
+
+// Uncompressed scanline OpenEXR with 32-bit float R, G, B and A channels.
+pub fn write_exr<W: Write>(w: &mut W, width: u32, height: u32, pixels: &[Vec4]) -> io::Result<()> {
+    fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+        for s in [name, kind] {
+            header.extend_from_slice(s.as_bytes());
+            header.push(0);
+        }
+        header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+        header.extend_from_slice(value);
+    }
+
+    // Channels have to be listed and stored in alphabetical order
+    let channels = ["A", "B", "G", "R"];
+    let mut list = Vec::new();
+    for name in channels.iter() {
+        list.extend_from_slice(name.as_bytes());
+        list.push(0);
+        // FLOAT, not linear, three reserved bytes and no subsampling
+        list.extend_from_slice(&2i32.to_le_bytes());
+        list.extend_from_slice(&[0, 0, 0, 0]);
+        list.extend_from_slice(&1i32.to_le_bytes());
+        list.extend_from_slice(&1i32.to_le_bytes());
+    }
+    list.push(0);
+
+    let mut window = Vec::new();
+    for v in [0, 0, width as i32 - 1, height as i32 - 1] {
+        window.extend_from_slice(&v.to_le_bytes());
+    }
+
+    let one = 1f32.to_le_bytes();
+    let mut header = vec![0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0];
+    attribute(&mut header, "channels", "chlist", &list);
+    attribute(&mut header, "compression", "compression", &[0]);
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    attribute(&mut header, "pixelAspectRatio", "float", &one);
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(&mut header, "screenWindowWidth", "float", &one);
+    header.push(0);
+    w.write_all(&header)?;
+
+    // One line per block, each holding its y and size before the data
+    let line_size = width as usize * channels.len() * 4;
+    let offset_table = height as u64 * 8;
+    for y in 0..height as u64 {
+        let offset = header.len() as u64 + offset_table + y * (8 + line_size as u64);
+        w.write_all(&offset.to_le_bytes())?;
+    }
+
+    let mut line = Vec::with_capacity(line_size);
+    for (y, row) in pixels.chunks(width as usize).enumerate() {
+        line.clear();
+        for channel in [3, 2, 1, 0] {
+            for p in row {
+                line.extend_from_slice(&p[channel].to_le_bytes());
+            }
+        }
+        w.write_all(&(y as i32).to_le_bytes())?;
+        w.write_all(&(line_size as i32).to_le_bytes())?;
+        w.write_all(&line)?;
+    }
+    Ok(())
+}
+
+// Radiance RGBE with flat scanlines, alpha is dropped.
+pub fn write_radiance<W: Write>(
+    w: &mut W,
+    width: u32,
+    height: u32,
+    pixels: &[Vec4],
+) -> io::Result<()> {
+    write!(
+        w,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        height, width
+    )?;
+    let mut line = Vec::with_capacity(width as usize * 4);
+    for row in pixels.chunks(width as usize) {
+        line.clear();
+        for p in row {
+            line.extend_from_slice(&rgbe(*p));
+        }
+        w.write_all(&line)?;
+    }
+    Ok(())
+}
+
+// A shared exponent with an 8-bit mantissa per channel.
+fn rgbe(c: Vec4) -> [u8; 4] {
+    let (r, g, b) = (c.x.max(0.), c.y.max(0.), c.z.max(0.));
+    let max = r.max(g).max(b);
+    if max < 1e-32 {
+        return [0; 4];
+    }
+
+    // max = m * 2^e with m in [0.5, 1)
+    let mut e = max.log2().floor() as i32 + 1;
+    if max * (-e as f32).exp2() >= 1. {
+        e += 1;
+    }
+    let scale = ((8 - e) as f32).exp2();
+    [
+        (r * scale) as u8,
+        (g * scale) as u8,
+        (b * scale) as u8,
+        (e + 128) as u8,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn i32_at(data: &[u8], at: usize) -> i32 {
+        i32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+    }
+
+    fn f32_at(data: &[u8], at: usize) -> f32 {
+        f32::from_bits(i32_at(data, at) as u32)
+    }
+
+    // Name, type and value of every header attribute, and where the header ends.
+    fn attributes(data: &[u8]) -> (Vec<(String, String, Vec<u8>)>, usize) {
+        let mut at = 8;
+        let read_string = |at: &mut usize| {
+            let end = *at + data[*at..].iter().position(|b| *b == 0).unwrap();
+            let s = String::from_utf8(data[*at..end].to_vec()).unwrap();
+            *at = end + 1;
+            s
+        };
+        let mut attributes = Vec::new();
+        loop {
+            let name = read_string(&mut at);
+            if name.is_empty() {
+                return (attributes, at);
+            }
+            let kind = read_string(&mut at);
+            let size = i32_at(data, at) as usize;
+            attributes.push((name, kind, data[at + 4..at + 4 + size].to_vec()));
+            at += 4 + size;
+        }
+    }
+
+    fn image() -> Vec<Vec4> {
+        vec![
+            Vec4::new(1., 2., 3., 4.),
+            Vec4::new(5., 6., 7., 8.),
+            Vec4::new(9., 10., 11., 12.),
+            Vec4::new(13., 14., 15., 16.),
+            Vec4::new(17., 18., 19., 20.),
+            Vec4::new(21., 22., 23., 24.),
+        ]
+    }
+
+    #[test]
+    fn exr_layout() {
+        let mut data = Vec::new();
+        write_exr(&mut data, 2, 3, &image()).unwrap();
+
+        assert_eq!(data[..4], [0x76, 0x2f, 0x31, 0x01]);
+        assert_eq!(i32_at(&data, 4), 2);
+
+        let (attributes, header_end) = attributes(&data);
+        let find = |name: &str| {
+            attributes
+                .iter()
+                .find(|a| a.0 == name)
+                .unwrap_or_else(|| panic!("no {} attribute", name))
+        };
+        let (_, kind, channels) = find("channels");
+        assert_eq!(kind, "chlist");
+        // Name, FLOAT, linear flag and reserved bytes, sampling, in alphabetical order
+        let names: Vec<u8> = channels.chunks(18).map(|c| c[0]).collect();
+        assert_eq!(names, b"ABGR\0");
+        assert!(channels.chunks(18).take(4).all(|c| i32_at(c, 2) == 2));
+
+        let (_, kind, window) = find("dataWindow");
+        assert_eq!(kind, "box2i");
+        let window: Vec<i32> = (0..4).map(|i| i32_at(window, 4 * i)).collect();
+        assert_eq!(window, [0, 0, 1, 2]);
+        assert_eq!(find("compression").2, [0]);
+
+        // An offset per line, each pointing at its y, its size and A, B, G and R in turn
+        let line_size = 2 * 4 * 4;
+        let table = header_end;
+        let first_line = table + 3 * 8;
+        for y in 0..3 {
+            // 64-bit offsets, small enough here for the upper half to be 0
+            let line = i32_at(&data, table + 8 * y) as usize;
+            assert_eq!(i32_at(&data, table + 8 * y + 4), 0);
+            assert_eq!(line, first_line + y * (8 + line_size));
+
+            assert_eq!(i32_at(&data, line), y as i32);
+            assert_eq!(i32_at(&data, line + 4), line_size as i32);
+            let values: Vec<f32> = (0..8).map(|i| f32_at(&data, line + 8 + 4 * i)).collect();
+            let p = 8. * y as f32;
+            assert_eq!(
+                values,
+                [
+                    p + 4.,
+                    p + 8.,
+                    p + 3.,
+                    p + 7.,
+                    p + 2.,
+                    p + 6.,
+                    p + 1.,
+                    p + 5.
+                ]
+            );
+        }
+        assert_eq!(data.len(), first_line + 3 * (8 + line_size));
+    }
+
+    #[test]
+    fn rgbe_encoding() {
+        assert_eq!(rgbe(Vec4::zero()), [0; 4]);
+        assert_eq!(rgbe(Vec4::new(-1., 0., 0., 1.)), [0; 4]);
+        // 1 is 0.5 * 2^1
+        assert_eq!(rgbe(Vec4::new(1., 1., 1., 1.)), [128, 128, 128, 129]);
+        assert_eq!(rgbe(Vec4::new(1., 0.5, 0., 1.)), [128, 64, 0, 129]);
+        // 3 is 0.75 * 2^2, the other channels share that exponent
+        assert_eq!(rgbe(Vec4::new(3., 1.5, 0.75, 1.)), [192, 96, 48, 130]);
+        assert_eq!(rgbe(Vec4::new(0.25, 0., 0., 1.)), [128, 0, 0, 127]);
+    }
+
+    #[test]
+    fn radiance_layout() {
+        let mut data = Vec::new();
+        write_radiance(&mut data, 2, 3, &image()).unwrap();
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 3 +X 2\n";
+        assert_eq!(data[..header.len()], header[..]);
+        assert_eq!(data.len(), header.len() + 6 * 4);
+        // The first pixel, 1 2 3 with 3 being 0.75 * 2^2
+        assert_eq!(data[header.len()..header.len() + 4], [64, 128, 192, 130]);
+    }
+}
@@ -0,0 +1,442 @@
+use crate::buffer::clamp;
+use crate::buffer::math::mat4::Mat4;
+use crate::buffer::math::vec3::Vec3;
+use crate::buffer::math::vec4::Vec4;
+use crate::buffer::mesh::Vertex;
+use crate::buffer::shader::Fragment;
+use crate::buffer::shader::FragmentShader;
+use crate::buffer::shader::Transforms;
+use crate::buffer::shader::VertexShader;
+
+// Light falloff with distance d: 1 / (constant + linear * d + quadratic * d^2).
+#[derive(Clone, Copy, Debug)]
+pub struct Attenuation {
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl Attenuation {
+    pub fn none() -> Attenuation {
+        Attenuation {
+            constant: 1.,
+            linear: 0.,
+            quadratic: 0.,
+        }
+    }
+
+    fn factor(&self, d: f32) -> f32 {
+        1. / (self.constant + self.linear * d + self.quadratic * d * d)
+    }
+}
+
+// Directions point the way the light travels. Spot angles are half-angles in degrees, full
+// intensity inside inner_angle fading out to nothing at outer_angle.
+#[derive(Clone, Copy, Debug)]
+pub enum Light {
+    Directional {
+        direction: Vec3,
+        color: Vec3,
+    },
+    Point {
+        position: Vec3,
+        color: Vec3,
+        attenuation: Attenuation,
+    },
+    Spot {
+        position: Vec3,
+        direction: Vec3,
+        color: Vec3,
+        attenuation: Attenuation,
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+}
+
+impl Light {
+    pub fn transform(&self, m: &Mat4) -> Light {
+        let point = |p: Vec3| (*m * p.extend(1.)).xyz();
+        let direction = |d: Vec3| (*m * d.extend(0.)).xyz();
+
+        match *self {
+            Light::Directional {
+                direction: d,
+                color,
+            } => Light::Directional {
+                direction: direction(d),
+                color,
+            },
+            Light::Point {
+                position,
+                color,
+                attenuation,
+            } => Light::Point {
+                position: point(position),
+                color,
+                attenuation,
+            },
+            Light::Spot {
+                position,
+                direction: d,
+                color,
+                attenuation,
+                inner_angle,
+                outer_angle,
+            } => Light::Spot {
+                position: point(position),
+                direction: direction(d),
+                color,
+                attenuation,
+                inner_angle,
+                outer_angle,
+            },
+        }
+    }
+
+    // Unit vector from the surface towards the light and the incoming radiance.
+    fn incident(&self, position: Vec3) -> (Vec3, Vec3) {
+        match *self {
+            Light::Directional { direction, color } => ((-direction).normalize_or_zero(), color),
+            Light::Point {
+                position: p,
+                color,
+                attenuation,
+            } => {
+                let to_light = p - position;
+                let d = to_light.length();
+                (to_light.normalize_or_zero(), color * attenuation.factor(d))
+            }
+            Light::Spot {
+                position: p,
+                direction,
+                color,
+                attenuation,
+                inner_angle,
+                outer_angle,
+            } => {
+                let to_light = p - position;
+                let d = to_light.length();
+                let l = to_light.normalize_or_zero();
+
+                let cos_inner = f32::cos(inner_angle * std::f32::consts::PI / 180.);
+                let cos_outer = f32::cos(outer_angle * std::f32::consts::PI / 180.);
+                let cos_theta = (-l).dot(direction.normalize_or_zero());
+                let cone = smoothstep(cos_outer, cos_inner, cos_theta);
+
+                (l, color * (attenuation.factor(d) * cone))
+            }
+        }
+    }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    if edge0 >= edge1 {
+        return if x >= edge1 { 1. } else { 0. };
+    }
+    let t = clamp((x - edge0) / (edge1 - edge0), 0., 1.);
+    t * t * (3. - 2. * t)
+}
+
+// alpha is handed on as the fragments' alpha, it only shows with a blend mode set.
+#[derive(Clone, Copy, Debug)]
+pub struct Material {
+    pub ambient: Vec3,
+    pub diffuse: Vec3,
+    pub specular: Vec3,
+    pub shininess: f32,
+    pub alpha: f32,
+}
+
+impl Material {
+    pub fn new(color: Vec3) -> Material {
+        Material {
+            ambient: color,
+            diffuse: color,
+            specular: Vec3::splat(1.),
+            shininess: 32.,
+            alpha: 1.,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SpecularModel {
+    Phong,
+    BlinnPhong,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ShadingMode {
+    Gouraud,
+    Phong,
+}
+
+#[derive(Clone, Debug)]
+pub struct Lighting {
+    pub lights: Vec<Light>,
+    pub ambient: Vec3,
+    pub model: SpecularModel,
+}
+
+impl Lighting {
+    pub fn new(ambient: Vec3) -> Lighting {
+        Lighting {
+            lights: Vec::new(),
+            ambient,
+            model: SpecularModel::BlinnPhong,
+        }
+    }
+
+    pub fn transform(&self, m: &Mat4) -> Lighting {
+        Lighting {
+            lights: self.lights.iter().map(|light| light.transform(m)).collect(),
+            ambient: self.ambient,
+            model: self.model,
+        }
+    }
+
+    // Position and normal have to be in the same space as the lights, with the eye at the
+    // origin.
+    pub fn illuminate(&self, material: &Material, position: Vec3, normal: Vec3) -> Vec3 {
+        let n = normal.normalize_or_zero();
+        let v = (-position).normalize_or_zero();
+
+        let mut color = self.ambient * material.ambient;
+        for light in self.lights.iter() {
+            let (l, radiance) = light.incident(position);
+
+            let diffuse = n.dot(l).max(0.);
+            if diffuse <= 0. {
+                continue;
+            }
+
+            let specular = match self.model {
+                SpecularModel::Phong => {
+                    let r = n * (2. * n.dot(l)) - l;
+                    r.dot(v).max(0.).powf(material.shininess)
+                }
+                SpecularModel::BlinnPhong => {
+                    let h = (l + v).normalize_or_zero();
+                    n.dot(h).max(0.).powf(material.shininess)
+                }
+            };
+
+            let lit = material.diffuse * diffuse + material.specular * specular;
+            color += lit * radiance;
+        }
+        color
+    }
+}
+
+// Lighting with the lights already moved into eye space, see Buffer::draw_mesh_lit.
+pub struct LightingUniforms {
+    pub lighting: Lighting,
+    pub material: Material,
+}
+
+fn to_eye(vertex: &Vertex, transforms: &Transforms) -> (Vec3, Vec3) {
+    let p = transforms.obj2world * vertex.position.extend(1.);
+    let n = transforms.normal * vertex.normal;
+    (p.xyz(), n.normalize_or_zero())
+}
+
+fn project(vertex: &Vertex, transforms: &Transforms) -> Vec4 {
+    transforms.obj2proj * vertex.position.extend(1.)
+}
+
+// Lights every vertex and interpolates the resulting color.
+pub struct GouraudShader;
+
+impl VertexShader for GouraudShader {
+    type Uniforms = LightingUniforms;
+    type Varying = Vec3;
+
+    fn vertex(
+        &self,
+        vertex: &Vertex,
+        transforms: &Transforms,
+        uniforms: &LightingUniforms,
+    ) -> (Vec4, Vec3) {
+        let (position, normal) = to_eye(vertex, transforms);
+        let color = uniforms
+            .lighting
+            .illuminate(&uniforms.material, position, normal);
+        (project(vertex, transforms), color)
+    }
+}
+
+impl FragmentShader for GouraudShader {
+    type Uniforms = LightingUniforms;
+    type Varying = Vec3;
+
+    fn fragment(
+        &self,
+        fragment: &Fragment<Vec3>,
+        uniforms: &LightingUniforms,
+    ) -> Option<(f32, f32, f32, f32)> {
+        let c = fragment.varying;
+        Some((c.x, c.y, c.z, uniforms.material.alpha))
+    }
+}
+
+// Interpolates eye-space position and normal and lights every pixel.
+pub struct PhongShader;
+
+impl VertexShader for PhongShader {
+    type Uniforms = LightingUniforms;
+    type Varying = (Vec3, Vec3);
+
+    fn vertex(
+        &self,
+        vertex: &Vertex,
+        transforms: &Transforms,
+        _: &LightingUniforms,
+    ) -> (Vec4, (Vec3, Vec3)) {
+        (project(vertex, transforms), to_eye(vertex, transforms))
+    }
+}
+
+impl FragmentShader for PhongShader {
+    type Uniforms = LightingUniforms;
+    type Varying = (Vec3, Vec3);
+
+    fn fragment(
+        &self,
+        fragment: &Fragment<(Vec3, Vec3)>,
+        uniforms: &LightingUniforms,
+    ) -> Option<(f32, f32, f32, f32)> {
+        let (position, normal) = fragment.varying;
+        let c = uniforms
+            .lighting
+            .illuminate(&uniforms.material, position, normal);
+        Some((c.x, c.y, c.z, uniforms.material.alpha))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+    }
+
+    fn assert_close_vec(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn attenuation() {
+        let attenuation = Attenuation {
+            constant: 1.,
+            linear: 0.5,
+            quadratic: 0.25,
+        };
+        assert_close(attenuation.factor(0.), 1.);
+        assert_close(attenuation.factor(2.), 1. / 3.);
+        assert_close(attenuation.factor(4.), 1. / 7.);
+        assert_close(Attenuation::none().factor(100.), 1.);
+    }
+
+    #[test]
+    fn directional_and_point_lights() {
+        let directional = Light::Directional {
+            direction: Vec3::new(0., -2., 0.),
+            color: Vec3::new(1., 0.5, 0.25),
+        };
+        for position in [Vec3::zero(), Vec3::new(10., -3., 7.)] {
+            let (l, radiance) = directional.incident(position);
+            assert_close_vec(l, Vec3::new(0., 1., 0.));
+            assert_close_vec(radiance, Vec3::new(1., 0.5, 0.25));
+        }
+
+        let point = Light::Point {
+            position: Vec3::new(0., 3., 4.),
+            color: Vec3::splat(1.),
+            attenuation: Attenuation {
+                constant: 0.,
+                linear: 0.,
+                quadratic: 1.,
+            },
+        };
+        let (l, radiance) = point.incident(Vec3::zero());
+        assert_close_vec(l, Vec3::new(0., 0.6, 0.8));
+        assert_close_vec(radiance, Vec3::splat(1. / 25.));
+    }
+
+    // Spot at the origin shining down -z, lighting a point at the given angle off its axis.
+    fn spot_cone(angle: f32) -> f32 {
+        let spot = Light::Spot {
+            position: Vec3::zero(),
+            direction: Vec3::new(0., 0., -1.),
+            color: Vec3::splat(1.),
+            attenuation: Attenuation::none(),
+            inner_angle: 10.,
+            outer_angle: 20.,
+        };
+        let a = angle.to_radians();
+        let (_, radiance) = spot.incident(Vec3::new(a.sin(), 0., -a.cos()));
+        radiance.x
+    }
+
+    #[test]
+    fn spot_cone_falloff() {
+        assert_close(spot_cone(0.), 1.);
+        assert_close(spot_cone(10.), 1.);
+        assert_close(spot_cone(20.), 0.);
+        assert_close(spot_cone(30.), 0.);
+
+        let cos = |degrees: f32| degrees.to_radians().cos();
+        let t = (cos(15.) - cos(20.)) / (cos(10.) - cos(20.));
+        assert_close(spot_cone(15.), t * t * (3. - 2. * t));
+
+        // Falls off steadily from the inner to the outer edge
+        let samples: Vec<f32> = (10..=20).map(|a| spot_cone(a as f32)).collect();
+        assert!(samples.windows(2).all(|w| w[0] > w[1]));
+    }
+
+    // Specular only, light coming in 45 degrees off the normal and the eye straight above.
+    fn specular(model: SpecularModel) -> f32 {
+        let a = 45f32.to_radians();
+        let mut lighting = Lighting::new(Vec3::zero());
+        lighting.model = model;
+        lighting.lights.push(Light::Directional {
+            direction: Vec3::new(-a.sin(), 0., -a.cos()),
+            color: Vec3::splat(1.),
+        });
+        let mut material = Material::new(Vec3::zero());
+        material.shininess = 2.;
+
+        let color = lighting.illuminate(&material, Vec3::new(0., 0., -1.), Vec3::new(0., 0., 1.));
+        color.x
+    }
+
+    #[test]
+    fn phong_and_blinn_specular() {
+        // The reflection is 45 degrees from the eye, cos^2 of that is 0.5
+        assert_close(specular(SpecularModel::Phong), 0.5);
+        // The half vector is 22.5 degrees from the normal
+        assert_close(
+            specular(SpecularModel::BlinnPhong),
+            22.5f32.to_radians().cos().powi(2),
+        );
+    }
+
+    #[test]
+    fn diffuse_and_ambient() {
+        let mut lighting = Lighting::new(Vec3::splat(0.2));
+        lighting.lights.push(Light::Directional {
+            direction: Vec3::new(0., 0., -1.),
+            color: Vec3::splat(0.5),
+        });
+        let mut material = Material::new(Vec3::new(1., 0.5, 0.));
+        material.specular = Vec3::zero();
+
+        let lit = lighting.illuminate(&material, Vec3::new(0., 0., -1.), Vec3::new(0., 0., 2.));
+        assert_close_vec(lit, Vec3::new(0.7, 0.35, 0.));
+
+        // Facing away only gets the ambient term
+        let unlit = lighting.illuminate(&material, Vec3::new(0., 0., -1.), Vec3::new(0., 0., -1.));
+        assert_close_vec(unlit, Vec3::new(0.2, 0.1, 0.));
+    }
+}
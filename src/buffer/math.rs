@@ -1,9 +1,11 @@
-pub mod slice_ops;
+#[macro_use]
+pub mod vector;
 
+pub mod mat3;
 pub mod mat4;
 pub mod matrix;
-#[macro_use]
-pub mod vector;
+pub mod quat;
+pub mod vec2;
 pub mod vec3;
 pub mod vec4;
 
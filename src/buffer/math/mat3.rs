@@ -0,0 +1,216 @@
+use std::ops::Index;
+use std::ops::IndexMut;
+use std::ops::Mul;
+
+use crate::buffer::math::matrix::Matrix;
+use crate::buffer::math::quat::Quat;
+use crate::buffer::math::vec3::Vec3;
+
+// Row-major like Mat4. Used as a 3D linear transform (rotations, scales, normal matrices),
+// except for translate which treats it as a 2D transform in homogeneous coordinates.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Mat3(pub [f32; 9]);
+
+impl From<[f32; 9]> for Mat3 {
+    fn from(m: [f32; 9]) -> Mat3 {
+        Mat3(m)
+    }
+}
+
+impl Index<usize> for Mat3 {
+    type Output = f32;
+
+    fn index(&self, i: usize) -> &f32 {
+        &self.0[i]
+    }
+}
+
+impl IndexMut<usize> for Mat3 {
+    fn index_mut(&mut self, i: usize) -> &mut f32 {
+        &mut self.0[i]
+    }
+}
+
+impl Mul for Mat3 {
+    type Output = Mat3;
+
+    fn mul(self, rhs: Mat3) -> Mat3 {
+        let mut m: Mat3 = Mat3::zeros();
+        for row in 0..3 {
+            for column in 0..3 {
+                m[row * 3 + column] = self[row * 3] * rhs[column]
+                    + self[row * 3 + 1] * rhs[3 + column]
+                    + self[row * 3 + 2] * rhs[6 + column];
+            }
+        }
+        m
+    }
+}
+
+impl Mul<Vec3> for Mat3 {
+    type Output = Vec3;
+
+    fn mul(self, v: Vec3) -> Vec3 {
+        Vec3::new(
+            self[0] * v.x + self[1] * v.y + self[2] * v.z,
+            self[3] * v.x + self[4] * v.y + self[5] * v.z,
+            self[6] * v.x + self[7] * v.y + self[8] * v.z,
+        )
+    }
+}
+
+impl Matrix for Mat3 {
+    type MatrixType = Mat3;
+    type VectorType = Vec3;
+
+    fn zeros() -> Self {
+        Mat3([0.; 9])
+    }
+    fn ones() -> Self {
+        Mat3([1.; 9])
+    }
+    fn identity() -> Self {
+        Mat3([1., 0., 0., 0., 1., 0., 0., 0., 1.])
+    }
+
+    fn transpose(&mut self) -> &mut Self {
+        self.0.swap(1, 3);
+        self.0.swap(2, 6);
+        self.0.swap(5, 7);
+
+        self
+    }
+
+    fn add(&mut self, rhs: &Self) -> &mut Self {
+        for i in 0..9 {
+            self[i] += rhs[i];
+        }
+
+        self
+    }
+    fn sub(&mut self, rhs: &Self) -> &mut Self {
+        for i in 0..9 {
+            self[i] -= rhs[i];
+        }
+
+        self
+    }
+
+    fn scale(&mut self, factor: f32) -> &mut Self {
+        for v in self.0.iter_mut() {
+            *v *= factor;
+        }
+
+        self
+    }
+
+    fn translate(&mut self, direction: &[f32]) -> &mut Self {
+        debug_assert!(direction.len() >= 2);
+
+        let x = direction[0];
+        let y = direction[1];
+
+        self[2] += self[0] * x + self[1] * y;
+        self[5] += self[3] * x + self[4] * y;
+        self[8] += self[6] * x + self[7] * y;
+
+        self
+    }
+
+    fn rotate(&mut self, angle: f32, axis: &[f32]) -> &mut Self {
+        const EPSILON: f32 = 1e-5_f32;
+
+        let x = axis[0];
+        let y = axis[1];
+        let z = axis[2];
+
+        let len = (x * x + y * y + z * z).sqrt();
+
+        if len.abs() <= EPSILON {
+            debug_assert!(len.abs() > EPSILON);
+            return self;
+        }
+
+        *self = *self * Quat::from_axis_angle(Vec3::new(x, y, z), angle).to_mat3();
+
+        self
+    }
+
+    fn determinant(&self) -> f32 {
+        self[0] * (self[4] * self[8] - self[5] * self[7])
+            - self[1] * (self[3] * self[8] - self[5] * self[6])
+            + self[2] * (self[3] * self[7] - self[4] * self[6])
+    }
+
+    fn inverse(&self) -> Option<Self> {
+        let det = self.determinant();
+        if det == 0. || !det.is_finite() {
+            return None;
+        }
+        let inv = 1. / det;
+
+        Some(Mat3([
+            (self[4] * self[8] - self[5] * self[7]) * inv,
+            (self[2] * self[7] - self[1] * self[8]) * inv,
+            (self[1] * self[5] - self[2] * self[4]) * inv,
+            (self[5] * self[6] - self[3] * self[8]) * inv,
+            (self[0] * self[8] - self[2] * self[6]) * inv,
+            (self[2] * self[3] - self[0] * self[5]) * inv,
+            (self[3] * self[7] - self[4] * self[6]) * inv,
+            (self[1] * self[6] - self[0] * self[7]) * inv,
+            (self[0] * self[4] - self[1] * self[3]) * inv,
+        ]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: &Mat3, b: &Mat3) {
+        let close =
+            a.0.iter()
+                .zip(b.0.iter())
+                .all(|(x, y)| (x - y).abs() < 1e-5);
+        assert!(close, "{:?} != {:?}", a, b);
+    }
+
+    const M: Mat3 = Mat3([1., 2., 3., 4., 5., 6., 7., 8., 10.]);
+
+    #[test]
+    fn determinant() {
+        assert_eq!(Mat3::identity().determinant(), 1.);
+        assert_eq!(
+            Mat3([2., 0., 0., 0., 3., 0., 0., 0., 4.]).determinant(),
+            24.
+        );
+        assert_eq!(M.determinant(), -3.);
+    }
+
+    #[test]
+    fn inverse() {
+        let expected = Mat3([-2. / 3., -4. / 3., 1., -2. / 3., 11. / 3., -2., 1., -2., 1.]);
+        let inverse = M.inverse().unwrap();
+        assert_close(&inverse, &expected);
+        assert_close(&(M * inverse), &Mat3::identity());
+        assert_close(&(inverse * M), &Mat3::identity());
+    }
+
+    #[test]
+    fn inverse_of_rotation_is_its_transpose() {
+        let mut m = Mat3::identity();
+        m.rotate(0.7, &[1., 2., 3.]);
+        let mut transpose = m;
+        transpose.transpose();
+        assert_close(&m.inverse().unwrap(), &transpose);
+    }
+
+    #[test]
+    fn singular_matrix_has_no_inverse() {
+        // The second row is twice the first
+        let m = Mat3([1., 2., 3., 2., 4., 6., 1., 1., 1.]);
+        assert_eq!(m.determinant(), 0.);
+        assert_eq!(m.inverse(), None);
+        assert_eq!(Mat3::zeros().inverse(), None);
+    }
+}
@@ -1,54 +1,56 @@
+use std::ops::Index;
+use std::ops::IndexMut;
+use std::ops::Mul;
+
+use crate::buffer::math::mat3::Mat3;
 use crate::buffer::math::matrix::Matrix;
+use crate::buffer::math::quat::Quat;
 use crate::buffer::math::vec3::Vec3;
 use crate::buffer::math::vec4::Vec4;
-use crate::buffer::math::vector::VecOps;
-use crate::buffer::math::vector::Vector;
 
 use std::f32;
 
-pub type Mat4 = [f32; 16];
-
-impl Matrix for Mat4 {
-    type MatrixType = Mat4;
-    type VectorType = Vec4;
+// Row-major, vectors are columns multiplied from the right: m * v.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Mat4(pub [f32; 16]);
 
-    fn zeros() -> Self {
-        [0.; 16]
+impl From<[f32; 16]> for Mat4 {
+    fn from(m: [f32; 16]) -> Mat4 {
+        Mat4(m)
     }
-    fn ones() -> Self {
-        [1.; 16]
-    }
-    fn identity() -> Self {
-        [
-            1., 0., 0., 0., 0., 1., 0., 0., 0., 0., 1., 0., 0., 0., 0., 1.,
-        ]
+}
+
+impl Index<usize> for Mat4 {
+    type Output = f32;
+
+    fn index(&self, i: usize) -> &f32 {
+        &self.0[i]
     }
+}
 
-    fn transpose(&mut self) -> &mut Self {
-        let v01 = self[1];
-        let v02 = self[2];
-        let v03 = self[3];
-        let v12 = self[6];
-        let v13 = self[7];
-        let v23 = self[11];
+impl IndexMut<usize> for Mat4 {
+    fn index_mut(&mut self, i: usize) -> &mut f32 {
+        &mut self.0[i]
+    }
+}
 
-        self[1] = self[4];
-        self[2] = self[8];
-        self[3] = self[12];
-        self[4] = v01;
-        self[6] = self[9];
-        self[7] = self[13];
-        self[8] = v02;
-        self[9] = v12;
-        self[11] = self[14];
-        self[12] = v03;
-        self[13] = v13;
-        self[14] = v23;
+impl Mul<Vec4> for Mat4 {
+    type Output = Vec4;
 
-        self
+    fn mul(self, v: Vec4) -> Vec4 {
+        Vec4::new(
+            self[0] * v.x + self[1] * v.y + self[2] * v.z + self[3] * v.w,
+            self[4] * v.x + self[5] * v.y + self[6] * v.z + self[7] * v.w,
+            self[8] * v.x + self[9] * v.y + self[10] * v.z + self[11] * v.w,
+            self[12] * v.x + self[13] * v.y + self[14] * v.z + self[15] * v.w,
+        )
     }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
 
-    fn mul(&mut self, rhs: &Self) -> Mat4 {
+    fn mul(self, rhs: Mat4) -> Mat4 {
         let r00 = rhs[0];
         let r01 = rhs[1];
         let r02 = rhs[2];
@@ -106,6 +108,47 @@ impl Matrix for Mat4 {
 
         m
     }
+}
+
+impl Matrix for Mat4 {
+    type MatrixType = Mat4;
+    type VectorType = Vec4;
+
+    fn zeros() -> Self {
+        Mat4([0.; 16])
+    }
+    fn ones() -> Self {
+        Mat4([1.; 16])
+    }
+    fn identity() -> Self {
+        Mat4([
+            1., 0., 0., 0., 0., 1., 0., 0., 0., 0., 1., 0., 0., 0., 0., 1.,
+        ])
+    }
+
+    fn transpose(&mut self) -> &mut Self {
+        let v01 = self[1];
+        let v02 = self[2];
+        let v03 = self[3];
+        let v12 = self[6];
+        let v13 = self[7];
+        let v23 = self[11];
+
+        self[1] = self[4];
+        self[2] = self[8];
+        self[3] = self[12];
+        self[4] = v01;
+        self[6] = self[9];
+        self[7] = self[13];
+        self[8] = v02;
+        self[9] = v12;
+        self[11] = self[14];
+        self[12] = v03;
+        self[13] = v13;
+        self[14] = v23;
+
+        self
+    }
 
     fn add(&mut self, rhs: &Self) -> &mut Self {
         for i in 0..16 {
@@ -171,57 +214,131 @@ impl Matrix for Mat4 {
             return self;
         }
 
-        x /= len;
-        y /= len;
-        z /= len;
+        *self = *self * Quat::from_axis_angle(Vec3::new(x, y, z), angle).to_mat4();
 
-        let (s, c) = angle.sin_cos();
-        let t = 1. - c;
+        self
+    }
 
-        let v00 = self[0];
-        let v01 = self[1];
-        let v02 = self[2];
-        let v03 = self[3];
-        let v10 = self[4];
-        let v11 = self[5];
-        let v12 = self[6];
-        let v13 = self[7];
-        let v20 = self[8];
-        let v21 = self[9];
-        let v22 = self[10];
-        let v23 = self[11];
+    fn determinant(&self) -> f32 {
+        let b00 = self[0] * self[5] - self[1] * self[4];
+        let b01 = self[0] * self[6] - self[2] * self[4];
+        let b02 = self[0] * self[7] - self[3] * self[4];
+        let b03 = self[1] * self[6] - self[2] * self[5];
+        let b04 = self[1] * self[7] - self[3] * self[5];
+        let b05 = self[2] * self[7] - self[3] * self[6];
+        let b06 = self[8] * self[13] - self[9] * self[12];
+        let b07 = self[8] * self[14] - self[10] * self[12];
+        let b08 = self[8] * self[15] - self[11] * self[12];
+        let b09 = self[9] * self[14] - self[10] * self[13];
+        let b10 = self[9] * self[15] - self[11] * self[13];
+        let b11 = self[10] * self[15] - self[11] * self[14];
+
+        b00 * b11 - b01 * b10 + b02 * b09 + b03 * b08 - b04 * b07 + b05 * b06
+    }
 
-        let rot00 = x * x * t + c;
-        let rot01 = y * x * t + z * s;
-        let rot02 = z * x * t - y * s;
-
-        let rot10 = x * y * t - z * s;
-        let rot11 = y * y * t + c;
-        let rot12 = z * y * t + x * s;
-
-        let rot20 = x * z * t + y * s;
-        let rot21 = y * z * t - x * s;
-        let rot22 = z * z * t + c;
-
-        self[0] = v00 * rot00 + v10 * rot01 + v20 * rot02;
-        self[1] = v01 * rot00 + v11 * rot01 + v21 * rot02;
-        self[2] = v02 * rot00 + v12 * rot01 + v22 * rot02;
-        self[3] = v03 * rot00 + v13 * rot01 + v23 * rot02;
-        self[4] = v00 * rot10 + v10 * rot11 + v20 * rot12;
-        self[5] = v01 * rot10 + v11 * rot11 + v21 * rot12;
-        self[6] = v02 * rot10 + v12 * rot11 + v22 * rot12;
-        self[7] = v03 * rot10 + v13 * rot11 + v23 * rot12;
-        self[8] = v00 * rot20 + v10 * rot21 + v20 * rot22;
-        self[9] = v01 * rot20 + v11 * rot21 + v21 * rot22;
-        self[10] = v02 * rot20 + v12 * rot21 + v22 * rot22;
-        self[11] = v03 * rot20 + v13 * rot21 + v23 * rot22;
+    fn inverse(&self) -> Option<Self> {
+        if self[12] == 0. && self[13] == 0. && self[14] == 0. && self[15] == 1. {
+            return self.inverse_affine();
+        }
 
-        self
+        let b00 = self[0] * self[5] - self[1] * self[4];
+        let b01 = self[0] * self[6] - self[2] * self[4];
+        let b02 = self[0] * self[7] - self[3] * self[4];
+        let b03 = self[1] * self[6] - self[2] * self[5];
+        let b04 = self[1] * self[7] - self[3] * self[5];
+        let b05 = self[2] * self[7] - self[3] * self[6];
+        let b06 = self[8] * self[13] - self[9] * self[12];
+        let b07 = self[8] * self[14] - self[10] * self[12];
+        let b08 = self[8] * self[15] - self[11] * self[12];
+        let b09 = self[9] * self[14] - self[10] * self[13];
+        let b10 = self[9] * self[15] - self[11] * self[13];
+        let b11 = self[10] * self[15] - self[11] * self[14];
+
+        let det = b00 * b11 - b01 * b10 + b02 * b09 + b03 * b08 - b04 * b07 + b05 * b06;
+        if det == 0. || !det.is_finite() {
+            return None;
+        }
+        let inv = 1. / det;
+
+        Some(Mat4([
+            (self[5] * b11 - self[6] * b10 + self[7] * b09) * inv,
+            (self[2] * b10 - self[1] * b11 - self[3] * b09) * inv,
+            (self[13] * b05 - self[14] * b04 + self[15] * b03) * inv,
+            (self[10] * b04 - self[9] * b05 - self[11] * b03) * inv,
+            (self[6] * b08 - self[4] * b11 - self[7] * b07) * inv,
+            (self[0] * b11 - self[2] * b08 + self[3] * b07) * inv,
+            (self[14] * b02 - self[12] * b05 - self[15] * b01) * inv,
+            (self[8] * b05 - self[10] * b02 + self[11] * b01) * inv,
+            (self[4] * b10 - self[5] * b08 + self[7] * b06) * inv,
+            (self[1] * b08 - self[0] * b10 - self[3] * b06) * inv,
+            (self[12] * b04 - self[13] * b02 + self[15] * b00) * inv,
+            (self[9] * b02 - self[8] * b04 - self[11] * b00) * inv,
+            (self[5] * b07 - self[4] * b09 - self[6] * b06) * inv,
+            (self[0] * b09 - self[1] * b07 + self[2] * b06) * inv,
+            (self[13] * b01 - self[12] * b03 - self[14] * b00) * inv,
+            (self[8] * b03 - self[9] * b01 + self[10] * b00) * inv,
+        ]))
     }
 }
 
+// Helpers for matrices whose last row is 0 0 0 1, translation in the last column.
+pub trait AffineMatrix {
+    // The upper-left 3x3 block, the matrix without its translation.
+    fn linear(&self) -> Mat3;
+    // Inverts the 3x3 block and the translation separately, only valid for affine matrices.
+    fn inverse_affine(&self) -> Option<Mat4>;
+    // Inverse transpose of the linear part, the matrix normals have to be transformed with
+    // to stay perpendicular to surfaces under non-uniform scaling.
+    fn inverse_transpose(&self) -> Option<Mat3>;
+}
+
+impl AffineMatrix for Mat4 {
+    fn linear(&self) -> Mat3 {
+        Mat3([
+            self[0], self[1], self[2], self[4], self[5], self[6], self[8], self[9], self[10],
+        ])
+    }
+
+    fn inverse_affine(&self) -> Option<Mat4> {
+        let a = self.linear().inverse()?;
+        let t = [self[3], self[7], self[11]];
+
+        let tx = -(a[0] * t[0] + a[1] * t[1] + a[2] * t[2]);
+        let ty = -(a[3] * t[0] + a[4] * t[1] + a[5] * t[2]);
+        let tz = -(a[6] * t[0] + a[7] * t[1] + a[8] * t[2]);
+
+        Some(Mat4([
+            a[0], a[1], a[2], tx, a[3], a[4], a[5], ty, a[6], a[7], a[8], tz, 0., 0., 0., 1.,
+        ]))
+    }
+
+    fn inverse_transpose(&self) -> Option<Mat3> {
+        let mut m = self.linear().inverse()?;
+        m.transpose();
+        Some(m)
+    }
+}
+
+// All of these map the view space -z axis into the clip volume -w <= x, y, z <= w, except
+// create_perspective_reversed which puts depth in 0 <= z <= w, near at w and far at 0.
+// fov_y is the full vertical angle in degrees.
 pub trait ProjectionMatrix {
     fn create_perspective(fov_y: f32, aspect_ratio: f32, near: f32, far: f32) -> Mat4;
+    // Perspective with the far plane at infinity, depth reaches 1 only in the limit.
+    fn create_perspective_infinite(fov_y: f32, aspect_ratio: f32, near: f32) -> Mat4;
+    // Maps near to depth 1 and far to 0, which spreads float precision evenly over the
+    // distance. far may be f32::INFINITY.
+    fn create_perspective_reversed(fov_y: f32, aspect_ratio: f32, near: f32, far: f32) -> Mat4;
+    // Off-axis perspective, the edges are given on the near plane.
+    fn create_frustum(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Mat4;
+    fn create_orthographic(
+        left: f32,
+        right: f32,
+        bottom: f32,
+        top: f32,
+        near: f32,
+        far: f32,
+    ) -> Mat4;
 }
 
 impl ProjectionMatrix for Mat4 {
@@ -229,7 +346,7 @@ impl ProjectionMatrix for Mat4 {
         fov_y *= std::f32::consts::PI / 360.;
         let f = 1. / (fov_y).tan();
         let nf = 1. / (near - far);
-        [
+        Mat4([
             f / aspect_ratio,
             0.,
             0.,
@@ -241,12 +358,118 @@ impl ProjectionMatrix for Mat4 {
             0.,
             0.,
             (far + near) * nf,
+            2. * far * near * nf,
+            0.,
+            0.,
+            -1.,
+            0.,
+        ])
+    }
+
+    fn create_perspective_infinite(mut fov_y: f32, aspect_ratio: f32, near: f32) -> Self {
+        fov_y *= std::f32::consts::PI / 360.;
+        let f = 1. / (fov_y).tan();
+        Mat4([
+            f / aspect_ratio,
+            0.,
+            0.,
+            0.,
+            0.,
+            f,
+            0.,
+            0.,
+            0.,
+            0.,
+            -1.,
+            -2. * near,
+            0.,
+            0.,
             -1.,
             0.,
+        ])
+    }
+
+    fn create_perspective_reversed(mut fov_y: f32, aspect_ratio: f32, near: f32, far: f32) -> Self {
+        fov_y *= std::f32::consts::PI / 360.;
+        let f = 1. / (fov_y).tan();
+        let (a, b) = if far.is_infinite() {
+            (0., near)
+        } else {
+            (near / (far - near), far * near / (far - near))
+        };
+        Mat4([
+            f / aspect_ratio,
+            0.,
+            0.,
+            0.,
+            0.,
+            f,
+            0.,
+            0.,
+            0.,
+            0.,
+            a,
+            b,
+            0.,
+            0.,
+            -1.,
+            0.,
+        ])
+    }
+
+    fn create_frustum(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Self {
+        let rl = 1. / (right - left);
+        let tb = 1. / (top - bottom);
+        let nf = 1. / (near - far);
+        Mat4([
+            2. * near * rl,
+            0.,
+            (right + left) * rl,
+            0.,
+            0.,
+            2. * near * tb,
+            (top + bottom) * tb,
             0.,
+            0.,
+            0.,
+            (far + near) * nf,
             2. * far * near * nf,
             0.,
-        ]
+            0.,
+            -1.,
+            0.,
+        ])
+    }
+
+    fn create_orthographic(
+        left: f32,
+        right: f32,
+        bottom: f32,
+        top: f32,
+        near: f32,
+        far: f32,
+    ) -> Self {
+        let rl = 1. / (right - left);
+        let tb = 1. / (top - bottom);
+        let nf = 1. / (near - far);
+        Mat4([
+            2. * rl,
+            0.,
+            0.,
+            -(right + left) * rl,
+            0.,
+            2. * tb,
+            0.,
+            -(top + bottom) * tb,
+            0.,
+            0.,
+            2. * nf,
+            (far + near) * nf,
+            0.,
+            0.,
+            0.,
+            1.,
+        ])
     }
 }
 
@@ -255,14 +478,10 @@ pub trait WorldMatrix {
 }
 
 impl WorldMatrix for Mat4 {
-    fn set_lookat(eye: Vec3, center: Vec3, mut up: Vec3) -> Mat4 {
-        let mut f: Vec3 = center;
-
-        f = f.normalize(f);
-        up = up.normalize(up);
-
-        let s: Vec3 = f.cross(f, up);
-        let u: Vec3 = s.cross(s, f);
+    fn set_lookat(eye: Vec3, center: Vec3, up: Vec3) -> Mat4 {
+        let f: Vec3 = (center - eye).normalize();
+        let s: Vec3 = f.cross(up.normalize()).normalize();
+        let u: Vec3 = s.cross(f);
 
         // let mut world2view: Mat4 = [
         //     s[0], u[0], -f[0], 0.,
@@ -270,14 +489,139 @@ impl WorldMatrix for Mat4 {
         //     s[2], u[2], -f[2], 0.,
         //     0., 0., 0., 1.,
         // ];
-        let mut world2view: Mat4 = [
+        let world2view: Mat4 = Mat4([
             s[0], s[1], s[2], 0., u[0], u[1], u[2], 0., -f[0], -f[1], -f[2], 0., 0., 0., 0., 1.,
-        ];
+        ]);
 
-        let m: Mat4 = [
+        let m: Mat4 = Mat4([
             1., 0., 0., -eye[0], 0., 1., 0., -eye[1], 0., 0., 1., -eye[2], 0., 0., 0., 1.,
-        ];
+        ]);
+
+        world2view * m
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: &Mat4, b: &Mat4) {
+        let close =
+            a.0.iter()
+                .zip(b.0.iter())
+                .all(|(x, y)| (x - y).abs() < 1e-5);
+        assert!(close, "{:?} != {:?}", a, b);
+    }
+
+    fn assert_close3(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    // Not affine, the last row is not 0 0 0 1
+    const M: Mat4 = Mat4([
+        2., 0., 1., 3., 1., 3., 0., 1., 0., 1., 4., 2., 1., 0., 2., 1.,
+    ]);
+
+    fn translation(x: f32, y: f32, z: f32) -> Mat4 {
+        Mat4([1., 0., 0., x, 0., 1., 0., y, 0., 0., 1., z, 0., 0., 0., 1.])
+    }
+
+    fn scale(x: f32, y: f32, z: f32) -> Mat4 {
+        Mat4([x, 0., 0., 0., 0., y, 0., 0., 0., 0., z, 0., 0., 0., 0., 1.])
+    }
+
+    // Scales by 2, 3 and 4, turns about a skewed axis and moves by (1, 2, 3)
+    fn affine() -> Mat4 {
+        let mut m = translation(1., 2., 3.);
+        m.rotate(0.7, &[1., 2., 3.]);
+        m * scale(2., 3., 4.)
+    }
+
+    #[test]
+    fn determinant() {
+        assert_eq!(Mat4::identity().determinant(), 1.);
+        assert_eq!(M.determinant(), -32.);
+        assert!((affine().determinant() - 24.).abs() < 1e-4);
+    }
+
+    #[test]
+    fn inverse() {
+        let expected = Mat4([
+            -1. / 16.,
+            5. / 32.,
+            -15. / 32.,
+            31. / 32.,
+            -1. / 8.,
+            5. / 16.,
+            1. / 16.,
+            -1. / 16.,
+            -3. / 16.,
+            -1. / 32.,
+            3. / 32.,
+            13. / 32.,
+            7. / 16.,
+            -3. / 32.,
+            9. / 32.,
+            -25. / 32.,
+        ]);
+        let inverse = M.inverse().unwrap();
+        assert_close(&inverse, &expected);
+        assert_close(&(M * inverse), &Mat4::identity());
+        assert_close(&(inverse * M), &Mat4::identity());
+    }
+
+    #[test]
+    fn inverse_of_projection() {
+        let m = Mat4::create_perspective(60., 1.5, 0.1, 100.);
+        assert_close(&(m * m.inverse().unwrap()), &Mat4::identity());
+    }
+
+    #[test]
+    fn inverse_affine() {
+        let inverse = translation(1., 2., 3.).inverse_affine().unwrap();
+        assert_close(&inverse, &translation(-1., -2., -3.));
+
+        let m = affine();
+        let inverse = m.inverse_affine().unwrap();
+        assert_close(&(m * inverse), &Mat4::identity());
+        assert_close(&(inverse * m), &Mat4::identity());
+        assert_eq!(m.inverse(), Some(inverse));
+    }
+
+    #[test]
+    fn singular_matrix_has_no_inverse() {
+        // The last row is the sum of the first two
+        let m = Mat4([
+            1., 2., 3., 4., 0., 1., 0., 1., 5., 6., 7., 8., 1., 3., 3., 5.,
+        ]);
+        assert_eq!(m.determinant(), 0.);
+        assert_eq!(m.inverse(), None);
+
+        // Affine with a zero scale, taking the affine path
+        let flat = affine() * scale(1., 0., 1.);
+        assert_eq!(flat.inverse(), None);
+        assert_eq!(flat.inverse_affine(), None);
+        assert_eq!(flat.inverse_transpose(), None);
+    }
 
-        world2view.mul(&m)
+    #[test]
+    fn normal_matrix_under_non_uniform_scale() {
+        let m = translation(5., 6., 7.) * scale(2., 4., 8.);
+        let expected = Mat3([0.5, 0., 0., 0., 0.25, 0., 0., 0., 0.125]);
+        assert_eq!(m.inverse_transpose(), Some(expected));
+
+        // The plane x = y, its normal stays perpendicular to the transformed tangent only
+        // with the normal matrix
+        let (tangent, normal) = (Vec3::new(1., 1., 0.), Vec3::new(1., -1., 0.));
+        let tangent = m.linear() * tangent;
+        assert!((m.inverse_transpose().unwrap() * normal).dot(tangent).abs() < 1e-6);
+        assert!((m.linear() * normal).dot(tangent).abs() > 1.);
+
+        // Rotations are left alone
+        let mut rotation = Mat4::identity();
+        rotation.rotate(0.7, &[1., 2., 3.]);
+        let n = Vec3::new(0., 0., 1.);
+        let normal_matrix = rotation.inverse_transpose().unwrap();
+        assert_close3(normal_matrix * n, rotation.linear() * n);
     }
 }
@@ -1,3 +1,4 @@
+// Products go through std::ops::Mul, matrix * matrix and matrix * column vector.
 pub trait Matrix {
     type MatrixType;
     type VectorType;
@@ -10,8 +11,6 @@ pub trait Matrix {
 
     fn transpose(&mut self) -> &mut Self::MatrixType;
 
-    fn mul(&mut self, rhs: &Self::MatrixType) -> Self::MatrixType;
-
     fn add(&mut self, rhs: &Self::MatrixType) -> &mut Self::MatrixType;
 
     fn sub(&mut self, rhs: &Self::MatrixType) -> &mut Self::MatrixType;
@@ -21,4 +20,9 @@ pub trait Matrix {
     fn translate(&mut self, direction: &[f32]) -> &mut Self::MatrixType;
 
     fn rotate(&mut self, angle: f32, axis: &[f32]) -> &mut Self::MatrixType;
+
+    fn determinant(&self) -> f32;
+
+    // None when the matrix is singular.
+    fn inverse(&self) -> Option<Self::MatrixType>;
 }
@@ -0,0 +1,307 @@
+use std::ops::Mul;
+
+use crate::buffer::math::mat3::Mat3;
+use crate::buffer::math::mat4::AffineMatrix;
+use crate::buffer::math::mat4::Mat4;
+use crate::buffer::math::vec3::Vec3;
+
+// Rotation quaternion, w is the scalar part. Angles are in radians and rotations are
+// counter-clockwise when looking down the axis towards the origin, like Mat3::rotate.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Quat {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Quat {
+    pub fn new(x: f32, y: f32, z: f32, w: f32) -> Quat {
+        Quat { x, y, z, w }
+    }
+
+    pub fn identity() -> Quat {
+        Quat::new(0., 0., 0., 1.)
+    }
+
+    // A zero axis gives the identity.
+    pub fn from_axis_angle(axis: Vec3, angle: f32) -> Quat {
+        let len = axis.length();
+        if len == 0. {
+            return Quat::identity();
+        }
+
+        let (s, c) = (angle * 0.5).sin_cos();
+        let v = axis * (s / len);
+        Quat::new(v.x, v.y, v.z, c)
+    }
+
+    // Rolls around z first, then pitches around x and finally yaws around y, the usual
+    // order for cameras and characters with y up.
+    pub fn from_euler(yaw: f32, pitch: f32, roll: f32) -> Quat {
+        Quat::from_axis_angle(Vec3::new(0., 1., 0.), yaw)
+            * Quat::from_axis_angle(Vec3::new(1., 0., 0.), pitch)
+            * Quat::from_axis_angle(Vec3::new(0., 0., 1.), roll)
+    }
+
+    // Expects a pure rotation, scaling has to be removed beforehand.
+    pub fn from_mat3(m: &Mat3) -> Quat {
+        let trace = m[0] + m[4] + m[8];
+        let q = if trace > 0. {
+            let s = (trace + 1.).sqrt() * 2.;
+            Quat::new(
+                (m[7] - m[5]) / s,
+                (m[2] - m[6]) / s,
+                (m[3] - m[1]) / s,
+                0.25 * s,
+            )
+        } else if m[0] > m[4] && m[0] > m[8] {
+            let s = (1. + m[0] - m[4] - m[8]).sqrt() * 2.;
+            Quat::new(
+                0.25 * s,
+                (m[1] + m[3]) / s,
+                (m[2] + m[6]) / s,
+                (m[7] - m[5]) / s,
+            )
+        } else if m[4] > m[8] {
+            let s = (1. + m[4] - m[0] - m[8]).sqrt() * 2.;
+            Quat::new(
+                (m[1] + m[3]) / s,
+                0.25 * s,
+                (m[5] + m[7]) / s,
+                (m[2] - m[6]) / s,
+            )
+        } else {
+            let s = (1. + m[8] - m[0] - m[4]).sqrt() * 2.;
+            Quat::new(
+                (m[2] + m[6]) / s,
+                (m[5] + m[7]) / s,
+                0.25 * s,
+                (m[3] - m[1]) / s,
+            )
+        };
+        q.normalize()
+    }
+
+    // Uses the rotation part only, translation is ignored.
+    pub fn from_mat4(m: &Mat4) -> Quat {
+        Quat::from_mat3(&m.linear())
+    }
+
+    pub fn to_mat3(&self) -> Mat3 {
+        let Quat { x, y, z, w } = *self;
+        Mat3([
+            1. - 2. * (y * y + z * z),
+            2. * (x * y - z * w),
+            2. * (x * z + y * w),
+            2. * (x * y + z * w),
+            1. - 2. * (x * x + z * z),
+            2. * (y * z - x * w),
+            2. * (x * z - y * w),
+            2. * (y * z + x * w),
+            1. - 2. * (x * x + y * y),
+        ])
+    }
+
+    pub fn to_mat4(&self) -> Mat4 {
+        let m = self.to_mat3();
+        Mat4([
+            m[0], m[1], m[2], 0., m[3], m[4], m[5], 0., m[6], m[7], m[8], 0., 0., 0., 0., 1.,
+        ])
+    }
+
+    pub fn dot(&self, other: &Quat) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    }
+
+    pub fn length(&self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(&self) -> Quat {
+        let len = self.length();
+        if len == 0. {
+            return Quat::identity();
+        }
+        Quat::new(self.x / len, self.y / len, self.z / len, self.w / len)
+    }
+
+    pub fn conjugate(&self) -> Quat {
+        Quat::new(-self.x, -self.y, -self.z, self.w)
+    }
+
+    pub fn inverse(&self) -> Quat {
+        let d = self.dot(self);
+        if d == 0. {
+            return Quat::identity();
+        }
+        let c = self.conjugate();
+        Quat::new(c.x / d, c.y / d, c.z / d, c.w / d)
+    }
+
+    pub fn rotate(&self, v: Vec3) -> Vec3 {
+        let u = Vec3::new(self.x, self.y, self.z);
+        let t = u.cross(v) * 2.;
+        v + t * self.w + u.cross(t)
+    }
+
+    // Normalized linear interpolation, cheaper than slerp but not constant speed.
+    pub fn nlerp(&self, other: &Quat, t: f32) -> Quat {
+        let b = self.shortest(other);
+        Quat::new(
+            self.x + (b.x - self.x) * t,
+            self.y + (b.y - self.y) * t,
+            self.z + (b.z - self.z) * t,
+            self.w + (b.w - self.w) * t,
+        )
+        .normalize()
+    }
+
+    pub fn slerp(&self, other: &Quat, t: f32) -> Quat {
+        let b = self.shortest(other);
+        let d = self.dot(&b);
+        // Nearly parallel, sin(theta) would be too small to divide by
+        if d > 0.9995 {
+            return self.nlerp(&b, t);
+        }
+
+        let theta = d.min(1.).acos();
+        let sin_theta = theta.sin();
+        let wa = ((1. - t) * theta).sin() / sin_theta;
+        let wb = (t * theta).sin() / sin_theta;
+        Quat::new(
+            self.x * wa + b.x * wb,
+            self.y * wa + b.y * wb,
+            self.z * wa + b.z * wb,
+            self.w * wa + b.w * wb,
+        )
+    }
+
+    // q and -q are the same rotation, pick the one on the short way from self.
+    fn shortest(&self, other: &Quat) -> Quat {
+        if self.dot(other) < 0. {
+            Quat::new(-other.x, -other.y, -other.z, -other.w)
+        } else {
+            *other
+        }
+    }
+}
+
+// a * b rotates by b first, then by a.
+impl Mul for Quat {
+    type Output = Quat;
+
+    fn mul(self, rhs: Quat) -> Quat {
+        Quat::new(
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    // q and -q are the same rotation.
+    fn assert_same_rotation(a: Quat, b: Quat) {
+        assert!((a.dot(&b).abs() - 1.).abs() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    fn axes() -> [Vec3; 4] {
+        [
+            Vec3::new(1., 0., 0.),
+            Vec3::new(0., 1., 0.),
+            Vec3::new(0., 0., 1.),
+            Vec3::new(1., -2., 3.),
+        ]
+    }
+
+    #[test]
+    fn matrix_round_trip() {
+        // Angles near half a turn take the branches of from_mat3 for a negative trace
+        for axis in axes().iter() {
+            for angle in [0., 0.3, -1.2, 2.5, PI, 3.1] {
+                let q = Quat::from_axis_angle(*axis, angle);
+                assert_same_rotation(Quat::from_mat3(&q.to_mat3()), q);
+                assert_same_rotation(Quat::from_mat4(&q.to_mat4()), q);
+            }
+        }
+    }
+
+    #[test]
+    fn rotate_matches_matrix() {
+        let v = Vec3::new(0.5, -1., 2.);
+        for axis in axes().iter() {
+            let q = Quat::from_axis_angle(*axis, 1.1);
+            assert_close(q.rotate(v), q.to_mat3() * v);
+        }
+
+        let quarter = Quat::from_axis_angle(Vec3::new(0., 0., 1.), PI / 2.);
+        assert_close(quarter.rotate(Vec3::new(1., 0., 0.)), Vec3::new(0., 1., 0.));
+        assert_eq!(Quat::from_axis_angle(Vec3::zero(), 1.), Quat::identity());
+    }
+
+    #[test]
+    fn euler_order() {
+        let (yaw, pitch, roll) = (0.7, -0.4, 1.3);
+        let q = Quat::from_euler(yaw, pitch, roll);
+        let v = Vec3::new(1., 2., 3.);
+
+        // Roll first, yaw last
+        let rolled = Quat::from_axis_angle(Vec3::new(0., 0., 1.), roll).rotate(v);
+        let pitched = Quat::from_axis_angle(Vec3::new(1., 0., 0.), pitch).rotate(rolled);
+        let yawed = Quat::from_axis_angle(Vec3::new(0., 1., 0.), yaw).rotate(pitched);
+        assert_close(q.rotate(v), yawed);
+    }
+
+    #[test]
+    fn slerp() {
+        let z = Vec3::new(0., 0., 1.);
+        let a = Quat::identity();
+        let b = Quat::from_axis_angle(z, PI / 2.);
+
+        assert_same_rotation(a.slerp(&b, 0.), a);
+        assert_same_rotation(a.slerp(&b, 1.), b);
+        assert_same_rotation(a.slerp(&b, 0.5), Quat::from_axis_angle(z, PI / 4.));
+        assert_same_rotation(a.slerp(&b, 0.25), Quat::from_axis_angle(z, PI / 8.));
+
+        // -b is the same rotation, the interpolation must still take the short way
+        let flipped = Quat::new(-b.x, -b.y, -b.z, -b.w);
+        assert_same_rotation(a.slerp(&flipped, 0.5), Quat::from_axis_angle(z, PI / 4.));
+
+        // 300 degrees one way is 60 the other, halfway is -30
+        let far = Quat::from_axis_angle(z, 5. * PI / 3.);
+        assert_same_rotation(a.slerp(&far, 0.5), Quat::from_axis_angle(z, -PI / 6.));
+    }
+
+    #[test]
+    fn nlerp() {
+        let a = Quat::from_axis_angle(Vec3::new(1., 0., 0.), 0.4);
+        let b = Quat::from_axis_angle(Vec3::new(0., 1., 1.), 2.);
+        for t in [0., 0.3, 0.5, 0.8, 1.] {
+            assert!((a.nlerp(&b, t).length() - 1.).abs() < 1e-5);
+        }
+        assert_same_rotation(a.nlerp(&b, 0.), a);
+        assert_same_rotation(a.nlerp(&b, 1.), b);
+
+        let z = Vec3::new(0., 0., 1.);
+        let half = Quat::identity().nlerp(&Quat::from_axis_angle(z, PI / 2.), 0.5);
+        assert_same_rotation(half, Quat::from_axis_angle(z, PI / 4.));
+    }
+
+    #[test]
+    fn inverse_undoes_rotation() {
+        let q = Quat::from_axis_angle(Vec3::new(1., -2., 3.), 0.9);
+        let v = Vec3::new(3., 1., -2.);
+        assert_close(q.inverse().rotate(q.rotate(v)), v);
+        assert_same_rotation(q * q.inverse(), Quat::identity());
+    }
+}
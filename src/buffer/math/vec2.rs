@@ -0,0 +1,19 @@
+use crate::buffer::math::vec3::Vec3;
+
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Vec2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl_vector_ops!(Vec2, 2, x: 0, y: 1);
+
+impl Vec2 {
+    pub fn extend(self, z: f32) -> Vec3 {
+        Vec3::new(self.x, self.y, z)
+    }
+
+    pub fn yx(self) -> Vec2 {
+        Vec2::new(self.y, self.x)
+    }
+}
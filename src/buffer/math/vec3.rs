@@ -1,24 +1,41 @@
-use crate::buffer::math::slice_ops::*;
-use crate::buffer::math::vector::VecOps;
-use crate::buffer::math::vector::Vector;
+use crate::buffer::math::vec2::Vec2;
+use crate::buffer::math::vec4::Vec4;
 
-pub type Vec3 = [f32; 3];
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Vec3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl_vector_ops!(Vec3, 3, x: 0, y: 1, z: 2);
+
+impl Vec3 {
+    pub fn cross(self, other: Vec3) -> Vec3 {
+        Vec3::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
 
-impl_vector!(Vec3, 3);
+    pub fn extend(self, w: f32) -> Vec4 {
+        Vec4::new(self.x, self.y, self.z, w)
+    }
+
+    pub fn xy(self) -> Vec2 {
+        Vec2::new(self.x, self.y)
+    }
 
-impl VecOps for Vec3 {
-    type VectorType = Vec3;
+    pub fn xz(self) -> Vec2 {
+        Vec2::new(self.x, self.z)
+    }
 
-    fn normalize(&self, mut vec: Self::VectorType) -> Self::VectorType {
-        let len = f32::sqrt(f32::powf(vec[0], 2.) + f32::powf(vec[1], 2.) + f32::powf(vec[2], 2.));
-        [vec[0] / len, vec[1] / len, vec[2] / len]
+    pub fn yz(self) -> Vec2 {
+        Vec2::new(self.y, self.z)
     }
 
-    fn cross(&self, vec_a: Self::VectorType, vec_b: Self::VectorType) -> Self::VectorType {
-        [
-            vec_a[1] * vec_b[2] - vec_a[2] * vec_b[1],
-            -(vec_a[0] * vec_b[2] - vec_a[2] * vec_b[0]),
-            vec_a[0] * vec_b[1] - vec_a[1] * vec_b[0],
-        ]
+    pub fn zyx(self) -> Vec3 {
+        Vec3::new(self.z, self.y, self.x)
     }
 }
@@ -1,76 +1,180 @@
-pub trait Vector {
-    type VectorType;
+// Component-wise arithmetic shared by the vector types. Vectors multiply and divide with
+// scalars as well as with each other, the latter component by component.
+macro_rules! impl_vector_ops {
+    ($type:ident, $n:expr, $($field:ident: $index:expr),+) => {
+        impl $type {
+            pub const fn new($($field: f32),+) -> $type {
+                $type { $($field),+ }
+            }
 
-    fn zeros() -> Self::VectorType;
+            pub const fn zero() -> $type {
+                $type { $($field: 0.),+ }
+            }
 
-    fn ones() -> Self::VectorType;
+            pub const fn splat(value: f32) -> $type {
+                $type { $($field: value),+ }
+            }
 
-    fn mul(&self, rhs: &[f32]) -> Self::VectorType;
+            pub fn dot(self, other: $type) -> f32 {
+                0. $(+ self.$field * other.$field)+
+            }
 
-    fn add(&self, rhs: &[f32]) -> Self::VectorType;
+            pub fn length_squared(self) -> f32 {
+                self.dot(self)
+            }
 
-    fn sub(&self, rhs: &[f32]) -> Self::VectorType;
+            pub fn length(self) -> f32 {
+                self.dot(self).sqrt()
+            }
 
-    fn scale(&self, factor: f32) -> Self::VectorType;
+            // Zero vectors come out as NaN, see normalize_or_zero.
+            pub fn normalize(self) -> $type {
+                self / self.length()
+            }
 
-    fn div(&self, factor: f32) -> Self::VectorType;
-}
+            pub fn normalize_or_zero(self) -> $type {
+                let len = self.length();
+                if len > 0. {
+                    self / len
+                } else {
+                    $type::zero()
+                }
+            }
 
-pub trait MulVectorMatrix<Matrix> {
-    type VectorType;
+            pub fn lerp(self, other: $type, t: f32) -> $type {
+                self + (other - self) * t
+            }
 
-    fn mul_matrix_left(&self, lhs: &Matrix) -> Self::VectorType;
+            pub fn min(self, other: $type) -> $type {
+                $type { $($field: self.$field.min(other.$field)),+ }
+            }
 
-    fn mul_matrix(&self, rhs: &Matrix) -> Self::VectorType;
-}
+            pub fn max(self, other: $type) -> $type {
+                $type { $($field: self.$field.max(other.$field)),+ }
+            }
 
-pub trait VecOps {
-    type VectorType;
+            pub fn to_array(self) -> [f32; $n] {
+                [$(self.$field),+]
+            }
+        }
 
-    fn normalize(&self, vec: Self::VectorType) -> Self::VectorType;
-    fn cross(&self, vec_a: Self::VectorType, vec_b: Self::VectorType) -> Self::VectorType;
-}
+        impl From<[f32; $n]> for $type {
+            fn from(a: [f32; $n]) -> $type {
+                $type { $($field: a[$index]),+ }
+            }
+        }
 
-macro_rules! impl_vector {
-    ($type:ty, $n:expr) => {
-        impl Vector for $type {
-            type VectorType = $type;
-            fn zeros() -> $type {
-                [0.; $n]
+        impl From<$type> for [f32; $n] {
+            fn from(v: $type) -> [f32; $n] {
+                v.to_array()
             }
+        }
 
-            fn ones() -> $type {
-                [1.; $n]
+        impl std::ops::Index<usize> for $type {
+            type Output = f32;
+
+            fn index(&self, i: usize) -> &f32 {
+                match i {
+                    $($index => &self.$field,)+
+                    _ => panic!("index {} out of range for {}", i, stringify!($type)),
+                }
             }
+        }
 
-            fn mul(&self, rhs: &[f32]) -> $type {
-                let mut dst = *self;
-                mul(&mut dst, rhs);
-                dst
+        impl std::ops::IndexMut<usize> for $type {
+            fn index_mut(&mut self, i: usize) -> &mut f32 {
+                match i {
+                    $($index => &mut self.$field,)+
+                    _ => panic!("index {} out of range for {}", i, stringify!($type)),
+                }
             }
+        }
 
-            fn add(&self, rhs: &[f32]) -> $type {
-                let mut dst = *self;
-                add(&mut dst, rhs);
-                dst
+        impl std::ops::Add for $type {
+            type Output = $type;
+
+            fn add(self, rhs: $type) -> $type {
+                $type { $($field: self.$field + rhs.$field),+ }
             }
+        }
 
-            fn sub(&self, rhs: &[f32]) -> $type {
-                let mut dst = *self;
-                sub(&mut dst, rhs);
-                dst
+        impl std::ops::Sub for $type {
+            type Output = $type;
+
+            fn sub(self, rhs: $type) -> $type {
+                $type { $($field: self.$field - rhs.$field),+ }
             }
+        }
 
-            fn scale(&self, factor: f32) -> $type {
-                let mut dst = *self;
-                scale(&mut dst, factor);
-                dst
+        impl std::ops::Mul for $type {
+            type Output = $type;
+
+            fn mul(self, rhs: $type) -> $type {
+                $type { $($field: self.$field * rhs.$field),+ }
             }
+        }
+
+        impl std::ops::Mul<f32> for $type {
+            type Output = $type;
+
+            fn mul(self, rhs: f32) -> $type {
+                $type { $($field: self.$field * rhs),+ }
+            }
+        }
+
+        impl std::ops::Mul<$type> for f32 {
+            type Output = $type;
+
+            fn mul(self, rhs: $type) -> $type {
+                rhs * self
+            }
+        }
+
+        impl std::ops::Div for $type {
+            type Output = $type;
+
+            fn div(self, rhs: $type) -> $type {
+                $type { $($field: self.$field / rhs.$field),+ }
+            }
+        }
+
+        impl std::ops::Div<f32> for $type {
+            type Output = $type;
+
+            fn div(self, rhs: f32) -> $type {
+                $type { $($field: self.$field / rhs),+ }
+            }
+        }
+
+        impl std::ops::Neg for $type {
+            type Output = $type;
+
+            fn neg(self) -> $type {
+                $type { $($field: -self.$field),+ }
+            }
+        }
+
+        impl std::ops::AddAssign for $type {
+            fn add_assign(&mut self, rhs: $type) {
+                *self = *self + rhs;
+            }
+        }
+
+        impl std::ops::SubAssign for $type {
+            fn sub_assign(&mut self, rhs: $type) {
+                *self = *self - rhs;
+            }
+        }
+
+        impl std::ops::MulAssign<f32> for $type {
+            fn mul_assign(&mut self, rhs: f32) {
+                *self = *self * rhs;
+            }
+        }
 
-            fn div(&self, factor: f32) -> $type {
-                let mut dst = *self;
-                div(&mut dst, factor);
-                dst
+        impl std::ops::DivAssign<f32> for $type {
+            fn div_assign(&mut self, rhs: f32) {
+                *self = *self / rhs;
             }
         }
     };
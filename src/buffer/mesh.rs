@@ -1,22 +1,32 @@
+use std::collections::HashMap;
+
+use crate::buffer::blend;
+use crate::buffer::clamp;
 use crate::buffer::color::Color;
+use crate::buffer::cull::Aabb;
 use crate::buffer::pixel::Pixel;
 use crate::buffer::Buffer;
 
 use crate::buffer::math::int3::Int3;
 use crate::buffer::math::mat4::Mat4;
-use crate::buffer::math::matrix::Matrix;
+use crate::buffer::math::vec2::Vec2;
 use crate::buffer::math::vec3::Vec3;
-use crate::buffer::math::vec4::Vec4;
-use crate::buffer::math::vector::MulVectorMatrix;
-use crate::buffer::math::vector::VecOps;
-use crate::buffer::math::vector::Vector;
 
 #[derive(Clone)]
 pub struct Vertex {
     pub position: Vec3,
     pub normal: Vec3,
+    pub uv: Vec2,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum NormalWeighting {
+    Area,
+    Angle,
 }
 
+// Triangles are wound clockwise when seen from the side their normal points to.
+#[derive(Clone)]
 pub struct Mesh {
     pub v_size: u32,
     pub t_size: u32,
@@ -33,6 +43,164 @@ impl Mesh {
             indices: Vec::new(),
         }
     }
+
+    // Around the vertices in object space, None for an empty mesh.
+    pub fn bounds(&self) -> Option<Aabb> {
+        Aabb::from_points(self.vertices.iter().map(|v| v.position))
+    }
+
+    // Reorders the triangles farthest first as seen through obj2view, for drawing the mesh
+    // blended. Has to be redone whenever the mesh or the eye moves.
+    pub fn sort_back_to_front(&mut self, obj2view: &Mat4) {
+        let vertices = &self.vertices;
+        blend::sort_back_to_front(&mut self.indices, obj2view, |[a, b, c]| {
+            (vertices[*a as usize].position
+                + vertices[*b as usize].position
+                + vertices[*c as usize].position)
+                / 3.
+        });
+    }
+
+    fn corners(&self, t: usize) -> [Vec3; 3] {
+        let [a, b, c] = self.indices[t];
+        [
+            self.vertices[a as usize].position,
+            self.vertices[b as usize].position,
+            self.vertices[c as usize].position,
+        ]
+    }
+
+    // Not normalized, its length is twice the area of the triangle.
+    fn face_normal(&self, t: usize) -> Vec3 {
+        let [a, b, c] = self.corners(t);
+        let e1 = b - a;
+        let e2 = c - a;
+        e2.cross(e1)
+    }
+
+    fn corner_normal(&self, t: usize, corner: usize, weighting: NormalWeighting) -> Vec3 {
+        let n = self.face_normal(t);
+        match weighting {
+            NormalWeighting::Area => n,
+            NormalWeighting::Angle => {
+                let p = self.corners(t);
+                let e1 = (p[(corner + 1) % 3] - p[corner]).normalize_or_zero();
+                let e2 = (p[(corner + 2) % 3] - p[corner]).normalize_or_zero();
+                let cos = clamp(e1.dot(e2), -1., 1.);
+                n.normalize_or_zero() * cos.acos()
+            }
+        }
+    }
+
+    // Corners grouped by vertex position, so vertices that were only split for uv seams
+    // still end up with the same normal.
+    fn corners_by_position(&self) -> HashMap<[u32; 3], Vec<(usize, usize)>> {
+        let mut groups: HashMap<[u32; 3], Vec<(usize, usize)>> = HashMap::new();
+        for t in 0..self.t_size as usize {
+            for corner in 0..3 {
+                let v = self.indices[t][corner] as usize;
+                groups
+                    .entry(position_key(self.vertices[v].position))
+                    .or_default()
+                    .push((t, corner));
+            }
+        }
+        groups
+    }
+
+    pub fn compute_smooth_normals(&mut self, weighting: NormalWeighting) {
+        let mut sums: HashMap<[u32; 3], Vec3> = HashMap::new();
+        for t in 0..self.t_size as usize {
+            for corner in 0..3 {
+                let v = self.indices[t][corner] as usize;
+                let n = self.corner_normal(t, corner, weighting);
+                let sum = sums
+                    .entry(position_key(self.vertices[v].position))
+                    .or_insert(Vec3::zero());
+                *sum += n;
+            }
+        }
+
+        for vertex in self.vertices.iter_mut() {
+            if let Some(sum) = sums.get(&position_key(vertex.position)) {
+                vertex.normal = sum.normalize_or_zero();
+            }
+        }
+    }
+
+    // Gives every triangle its own three vertices carrying the face normal.
+    pub fn compute_flat_normals(&mut self) {
+        let mut vertices = Vec::with_capacity(3 * self.t_size as usize);
+        let mut indices = Vec::with_capacity(self.t_size as usize);
+
+        for t in 0..self.t_size as usize {
+            let n = self.face_normal(t).normalize_or_zero();
+            for corner in 0..3 {
+                let mut vertex = self.vertices[self.indices[t][corner] as usize].clone();
+                vertex.normal = n;
+                vertices.push(vertex);
+            }
+            let base = 3 * t as u32;
+            indices.push([base, base + 1, base + 2]);
+        }
+
+        self.vertices = vertices;
+        self.indices = indices;
+        self.v_size = self.vertices.len() as u32;
+        self.t_size = self.indices.len() as u32;
+    }
+
+    // Smooths across edges where the faces meet at less than crease_angle degrees and keeps
+    // the others sharp, splitting vertices where needed.
+    pub fn compute_crease_normals(&mut self, crease_angle: f32, weighting: NormalWeighting) {
+        let cos_crease = f32::cos(crease_angle * std::f32::consts::PI / 180.);
+        let faces: Vec<Vec3> = (0..self.t_size as usize)
+            .map(|t| self.face_normal(t).normalize_or_zero())
+            .collect();
+        let groups = self.corners_by_position();
+
+        let mut vertices: Vec<Vertex> = Vec::new();
+        let mut indices: Vec<Int3> = Vec::with_capacity(self.t_size as usize);
+        let mut split: HashMap<(u32, [u32; 3]), u32> = HashMap::new();
+
+        for t in 0..self.t_size as usize {
+            let mut triangle: Int3 = [0, 0, 0];
+            for (corner, index) in triangle.iter_mut().enumerate() {
+                let v = self.indices[t][corner];
+                let position = self.vertices[v as usize].position;
+
+                let mut sum = Vec3::zero();
+                for &(t2, c2) in groups[&position_key(position)].iter() {
+                    if faces[t].dot(faces[t2]) >= cos_crease {
+                        sum += self.corner_normal(t2, c2, weighting);
+                    }
+                }
+                let n = sum.normalize_or_zero();
+
+                *index = *split.entry((v, position_key(n))).or_insert_with(|| {
+                    let mut vertex = self.vertices[v as usize].clone();
+                    vertex.normal = n;
+                    vertices.push(vertex);
+                    vertices.len() as u32 - 1
+                });
+            }
+            indices.push(triangle);
+        }
+
+        self.vertices = vertices;
+        self.indices = indices;
+        self.v_size = self.vertices.len() as u32;
+        self.t_size = self.indices.len() as u32;
+    }
+}
+
+fn position_key(v: Vec3) -> [u32; 3] {
+    // + 0. turns -0. into 0. so both hash the same
+    [
+        (v.x + 0.).to_bits(),
+        (v.y + 0.).to_bits(),
+        (v.z + 0.).to_bits(),
+    ]
 }
 
 pub trait Triangle {
@@ -61,16 +229,39 @@ pub trait Torus {
 
 impl Render for Mesh {
     fn render(&mut self, buf: &mut Buffer) {
-        for i in 0..self.t_size {
-            buf.draw_triangle(
-                self.vertices[self.indices[i as usize][0] as usize].position,
-                self.vertices[self.indices[i as usize][1] as usize].position,
-                self.vertices[self.indices[i as usize][2] as usize].position,
-                Color { r: 255, g: 0, b: 0 },
-                Color { r: 0, g: 255, b: 0 },
-                Color { r: 0, g: 0, b: 255 },
-            )
+        if buf.cull_mesh(self) {
+            buf.clear_object_matrices();
+            return;
         }
+        const RED: Color = Color {
+            r: 255,
+            g: 0,
+            b: 0,
+            a: 255,
+        };
+        const GREEN: Color = Color {
+            r: 0,
+            g: 255,
+            b: 0,
+            a: 255,
+        };
+        const BLUE: Color = Color {
+            r: 0,
+            g: 0,
+            b: 255,
+            a: 255,
+        };
+        let position = |i: u32| self.vertices[i as usize].position;
+        let triangles = self.indices[..self.t_size as usize]
+            .iter()
+            .map(|[a, b, c]| {
+                [
+                    (position(*a), RED),
+                    (position(*b), GREEN),
+                    (position(*c), BLUE),
+                ]
+            });
+        buf.draw_triangles(triangles);
         buf.clear_object_matrices();
     }
 }
@@ -81,66 +272,90 @@ impl Triangle for Mesh {
         self.t_size = 1;
         self.vertices = vec![
             Vertex {
-                position: [0., 0., 0.],
-                normal: [0., 0., 0.]
+                position: Vec3::zero(),
+                normal: Vec3::zero(),
+                uv: Vec2::zero(),
             };
             self.v_size as usize
         ];
         self.indices = vec![[0, 0, 0]; self.t_size as usize];
 
-        self.vertices[0].position = [-0.5, 0., 0.];
-        self.vertices[1].position = [0., 0.5, 0.];
-        self.vertices[2].position = [0.5, 0., 0.];
+        self.vertices[0].position = Vec3::new(-0.5, 0., 0.);
+        self.vertices[1].position = Vec3::new(0., 0.5, 0.);
+        self.vertices[2].position = Vec3::new(0.5, 0., 0.);
+
+        self.vertices[0].uv = Vec2::new(0., 1.);
+        self.vertices[1].uv = Vec2::new(0.5, 0.);
+        self.vertices[2].uv = Vec2::new(1., 1.);
 
         self.indices[0] = [0, 1, 2];
+
+        self.compute_smooth_normals(NormalWeighting::Angle);
     }
 }
 
 impl Cube for Mesh {
     fn new(&mut self) {
-        self.v_size = 8;
-        self.t_size = 12;
-        self.vertices = vec![
-            Vertex {
-                position: [0., 0., 0.],
-                normal: [0., 0., 0.]
-            };
-            self.v_size as usize
+        let corners: [Vec3; 8] = [
+            Vec3::new(-0.5, 0.5, 0.5),
+            Vec3::new(0.5, 0.5, 0.5),
+            Vec3::new(0.5, -0.5, 0.5),
+            Vec3::new(-0.5, -0.5, 0.5),
+            Vec3::new(-0.5, 0.5, -0.5),
+            Vec3::new(0.5, 0.5, -0.5),
+            Vec3::new(0.5, -0.5, -0.5),
+            Vec3::new(-0.5, -0.5, -0.5),
         ];
-        self.indices = vec![[0, 0, 0]; self.t_size as usize];
+        // Every face gets its own four corners so it can carry a full [0, 1] texture square
+        let faces: [[usize; 4]; 6] = [
+            [0, 1, 2, 3],
+            [4, 0, 3, 7],
+            [1, 5, 6, 2],
+            [0, 4, 5, 1],
+            [6, 7, 3, 2],
+            [7, 6, 5, 4],
+        ];
+        let uvs: [Vec2; 4] = [
+            Vec2::new(0., 0.),
+            Vec2::new(1., 0.),
+            Vec2::new(1., 1.),
+            Vec2::new(0., 1.),
+        ];
+
+        self.vertices = Vec::new();
+        self.indices = Vec::new();
+
+        for face in faces.iter() {
+            let base = self.vertices.len() as u32;
+            for (corner, uv) in face.iter().zip(uvs.iter()) {
+                self.vertices.push(Vertex {
+                    position: corners[*corner],
+                    normal: Vec3::zero(),
+                    uv: *uv,
+                });
+            }
+            self.indices.push([base, base + 1, base + 2]);
+            self.indices.push([base, base + 2, base + 3]);
+        }
 
-        self.vertices[0].position = [-0.5, 0.5, 0.5];
-        self.vertices[1].position = [0.5, 0.5, 0.5];
-        self.vertices[2].position = [0.5, -0.5, 0.5];
-        self.vertices[3].position = [-0.5, -0.5, 0.5];
-        self.vertices[4].position = [-0.5, 0.5, -0.5];
-        self.vertices[5].position = [0.5, 0.5, -0.5];
-        self.vertices[6].position = [0.5, -0.5, -0.5];
-        self.vertices[7].position = [-0.5, -0.5, -0.5];
-
-        self.indices[0] = [0, 1, 3];
-        self.indices[1] = [1, 2, 3];
-        self.indices[2] = [4, 0, 3];
-        self.indices[3] = [7, 4, 3];
-        self.indices[4] = [2, 1, 5];
-        self.indices[5] = [2, 5, 6];
-        self.indices[6] = [0, 4, 5];
-        self.indices[7] = [5, 1, 0];
-        self.indices[8] = [6, 7, 3];
-        self.indices[9] = [3, 2, 6];
-        self.indices[10] = [7, 5, 4];
-        self.indices[11] = [7, 6, 5];
+        self.v_size = self.vertices.len() as u32;
+        self.t_size = self.indices.len() as u32;
+
+        self.compute_crease_normals(45., NormalWeighting::Angle);
     }
 }
 
 impl Sphere for Mesh {
     fn new(&mut self, vert: u32, horiz: u32) {
-        self.v_size = vert * (horiz + 2);
+        // The first column is repeated at the end so the texture seam does not wrap around
+        let stride = vert + 1;
+        self.v_size = stride * (horiz + 2);
         self.t_size = 2 * vert * horiz;
         self.vertices = vec![
             Vertex {
-                position: [0., 0., 0.],
-                normal: [0., 0., 0.]
+                position: Vec3::zero(),
+                normal: Vec3::zero(),
+                uv: Vec2::zero(),
             };
             self.v_size as usize
         ];
@@ -149,39 +364,46 @@ impl Sphere for Mesh {
         for yy in 0..horiz + 2 {
             let y = f32::cos(yy as f32 * std::f32::consts::PI / (horiz as f32 + 1.));
             let r = f32::sqrt(1. - (y * y));
-            for rr in 0..vert {
+            for rr in 0..stride {
                 let x = r * f32::cos(2. * std::f32::consts::PI * rr as f32 / vert as f32);
                 let z = r * f32::sin(2. * std::f32::consts::PI * rr as f32 / vert as f32);
-                self.vertices[(rr + yy * vert) as usize].position = [x, y, z];
+                self.vertices[(rr + yy * stride) as usize].position = Vec3::new(x, y, z);
+                self.vertices[(rr + yy * stride) as usize].uv =
+                    Vec2::new(rr as f32 / vert as f32, yy as f32 / (horiz as f32 + 1.));
             }
         }
 
         for yy in 0..horiz {
             for rr in 0..vert {
                 self.indices[(rr + 2 * yy * vert) as usize] = [
-                    (rr + 1) % vert + yy * vert,
-                    rr + vert + yy * vert,
-                    (rr + 1) % vert + vert + yy * vert,
+                    rr + 1 + yy * stride,
+                    rr + stride + yy * stride,
+                    rr + 1 + stride + yy * stride,
                 ];
                 self.indices[(rr + vert + 2 * yy * vert) as usize] = [
-                    rr + vert + yy * vert,
-                    rr + 2 * vert + yy * vert,
-                    (rr + 1) % vert + vert + yy * vert,
+                    rr + stride + yy * stride,
+                    rr + 2 * stride + yy * stride,
+                    rr + 1 + stride + yy * stride,
                 ];
             }
         }
+
+        self.compute_smooth_normals(NormalWeighting::Angle);
     }
 }
 
 impl Cone for Mesh {
     fn new(&mut self, vert: u32, radius: f32, h: f32) {
         let horiz = 1;
-        self.v_size = vert * (horiz + 2);
+        // The first column is repeated at the end so the texture seam does not wrap around
+        let stride = vert + 1;
+        self.v_size = stride * (horiz + 2);
         self.t_size = 2 * vert * horiz;
         self.vertices = vec![
             Vertex {
-                position: [0., 0., 0.],
-                normal: [0., 0., 0.]
+                position: Vec3::zero(),
+                normal: Vec3::zero(),
+                uv: Vec2::zero(),
             };
             self.v_size as usize
         ];
@@ -190,27 +412,36 @@ impl Cone for Mesh {
         for yy in 0..horiz + 1 {
             let y = f32::cos(yy as f32 * std::f32::consts::PI / (horiz as f32 + 1.));
             let r = f32::sqrt(1. - (y * y)) * radius;
-            for rr in 0..vert {
+            for rr in 0..stride {
                 let x = r * f32::cos(2. * std::f32::consts::PI * rr as f32 / vert as f32);
                 let z = r * f32::sin(2. * std::f32::consts::PI * rr as f32 / vert as f32);
-                self.vertices[(rr + yy * vert) as usize].position = [x, y * h, z];
+                self.vertices[(rr + yy * stride) as usize].position = Vec3::new(x, y * h, z);
+            }
+        }
+
+        for yy in 0..horiz + 2 {
+            for rr in 0..stride {
+                self.vertices[(rr + yy * stride) as usize].uv =
+                    Vec2::new(rr as f32 / vert as f32, yy as f32 / (horiz as f32 + 1.));
             }
         }
 
         for yy in 0..horiz {
             for rr in 0..vert {
                 self.indices[(rr + 2 * yy * vert) as usize] = [
-                    (rr + 1) % vert + yy * vert,
-                    rr + vert + yy * vert,
-                    (rr + 1) % vert + vert + yy * vert,
+                    rr + 1 + yy * stride,
+                    rr + stride + yy * stride,
+                    rr + 1 + stride + yy * stride,
                 ];
                 self.indices[(rr + vert + 2 * yy * vert) as usize] = [
-                    rr + vert + yy * vert,
-                    rr + 2 * vert + yy * vert,
-                    (rr + 1) % vert + vert + yy * vert,
+                    rr + stride + yy * stride,
+                    rr + 2 * stride + yy * stride,
+                    rr + 1 + stride + yy * stride,
                 ];
             }
         }
+
+        self.compute_crease_normals(60., NormalWeighting::Angle);
     }
 }
 
@@ -237,8 +468,9 @@ impl Torus for Mesh {
                 let z = inner_rad * sin_slices;
 
                 self.vertices.push(Vertex {
-                    position: [x, y, z],
-                    normal: [0., 0., 0.],
+                    position: Vec3::new(x, y, z),
+                    normal: Vec3::zero(),
+                    uv: Vec2::new(u, v),
                 });
             }
         }
@@ -249,13 +481,116 @@ impl Torus for Mesh {
             let mut v2 = v1 + vertsPerSlice;
             /* outer ring */
             for j in 0..loops {
-                self.indices.push([v1, v1 + 1, v2]);
-                self.indices.push([v2, v1 + 1, v2 + 1]);
+                self.indices.push([v1, v2, v1 + 1]);
+                self.indices.push([v2, v2 + 1, v1 + 1]);
                 v1 += 1;
                 v2 += 1;
             }
         }
         self.v_size = self.vertices.len() as u32;
         self.t_size = self.indices.len() as u32;
+
+        self.compute_smooth_normals(NormalWeighting::Angle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cube() -> Mesh {
+        let mut cube = Mesh::construct();
+        <Mesh as Cube>::new(&mut cube);
+        cube
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    // The outward axis of the cube face the triangle lies on.
+    fn face_axis(mesh: &Mesh, t: &Int3) -> Vec3 {
+        let c = t.iter().fold(Vec3::zero(), |sum, i| {
+            sum + mesh.vertices[*i as usize].position
+        });
+        let (x, y, z) = (c.x.abs(), c.y.abs(), c.z.abs());
+        if x > y && x > z {
+            Vec3::new(c.x.signum(), 0., 0.)
+        } else if y > z {
+            Vec3::new(0., c.y.signum(), 0.)
+        } else {
+            Vec3::new(0., 0., c.z.signum())
+        }
+    }
+
+    fn assert_faces_flat(mesh: &Mesh) {
+        for t in mesh.indices.iter() {
+            for i in t.iter() {
+                assert_close(mesh.vertices[*i as usize].normal, face_axis(mesh, t));
+            }
+        }
+    }
+
+    #[test]
+    fn flat_normals_are_axis_aligned() {
+        let mut cube = cube();
+        cube.compute_flat_normals();
+        assert_eq!(cube.t_size, 12);
+        assert_eq!(cube.v_size, 36);
+        assert_faces_flat(&cube);
+    }
+
+    #[test]
+    fn smooth_normals_weld_corners() {
+        let mut cube = cube();
+        cube.compute_smooth_normals(NormalWeighting::Angle);
+        for v in cube.vertices.iter() {
+            // Corners sit at +-0.5 on every axis, so the diagonal is the position scaled up
+            assert_close(v.normal, v.position.normalize_or_zero());
+        }
+    }
+
+    #[test]
+    fn crease_splits_right_angles() {
+        // Cube::new already keeps its 90 degree edges below a 45 degree crease
+        assert_faces_flat(&cube());
+
+        let mut cube = cube();
+        cube.compute_crease_normals(95., NormalWeighting::Angle);
+        for v in cube.vertices.iter() {
+            assert_close(v.normal, v.position.normalize_or_zero());
+        }
+        // Every vertex keeps a single normal, nothing had to be split
+        assert_eq!(cube.v_size, 24);
+    }
+
+    #[test]
+    fn crease_keeps_shallow_folds_smooth() {
+        // Two triangles meeting along the x axis, folded up by 10 degrees
+        let fold = f32::sin(10. * std::f32::consts::PI / 180.);
+        let mut mesh = Mesh::construct();
+        for position in [
+            Vec3::new(0., 0., 0.),
+            Vec3::new(1., 0., 0.),
+            Vec3::new(0.5, -1., 0.),
+            Vec3::new(0.5, 1., -fold),
+        ] {
+            mesh.vertices.push(Vertex {
+                position,
+                normal: Vec3::zero(),
+                uv: Vec2::zero(),
+            });
+        }
+        mesh.indices = vec![[0, 1, 2], [1, 0, 3]];
+        mesh.v_size = 4;
+        mesh.t_size = 2;
+
+        let mut smooth = mesh.clone();
+        smooth.compute_crease_normals(30., NormalWeighting::Area);
+        assert_eq!(smooth.v_size, 4);
+
+        let mut sharp = mesh;
+        sharp.compute_crease_normals(5., NormalWeighting::Area);
+        assert_eq!(sharp.v_size, 6);
     }
 }
@@ -1,52 +1,160 @@
+#[cfg(feature = "image-io")]
 extern crate image;
 
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+
+pub mod blend;
+use blend::BlendMode;
+
+pub mod camera;
+
+pub mod clip;
+use clip::ClipVertex;
+
 pub mod color;
 use color::Color;
+use color::ColorSpace;
+
+pub mod cull;
+use cull::CullMode;
+use cull::CullStats;
+use cull::FrontFace;
+
+pub mod depth;
+use depth::DepthBias;
+use depth::DepthCompare;
+
+#[cfg(feature = "image-io")]
+pub mod gltf;
+
+pub mod hdr;
+
+pub mod light;
+use light::GouraudShader;
+use light::Lighting;
+use light::LightingUniforms;
+use light::Material;
+use light::PhongShader;
+use light::ShadingMode;
 
 pub mod math;
+use math::mat4::AffineMatrix;
 use math::mat4::Mat4;
 use math::matrix::Matrix;
+use math::quat::Quat;
 
 use math::vec3::Vec3;
 use math::vec4::Vec4;
-use math::vector::MulVectorMatrix;
-use math::vector::VecOps;
-use math::vector::Vector;
+
+pub mod obj;
 
 pub mod pixel;
+use pixel::Encoding;
 use pixel::Pixel;
 
+pub mod raster;
+use raster::Multisample;
+use raster::OutputState;
+use raster::ScreenTriangle;
+use raster::Target;
+
 pub mod mesh;
 use mesh::Mesh;
+use mesh::Vertex;
+
+pub mod shader;
 
+mod simd;
+use shader::FragmentShader;
+use shader::Transforms;
+use shader::Varying;
+use shader::VertexColor;
+use shader::VertexShader;
+
+pub mod texture;
+
+pub mod tonemap;
+use tonemap::ToneMapping;
+
+#[cfg(feature = "image-io")]
 pub trait Savable {
-    fn save_to_png(&self, path: &str);
+    fn save_to_png(&self, path: &str) -> Result<(), image::ImageError>;
+
+    // Keeps the alpha channel, for images meant to be composited.
+    fn save_to_png_rgba(&self, path: &str) -> Result<(), image::ImageError>;
 }
 
+// Meant to be kept across frames, clearing reuses the allocations. data holds width * height
+// pixels, rows stored top to bottom. depth and, when multisampling, sample_data hold the samples
+// of each pixel next to each other in the same order. With multisampling draws only touch the
+// samples and data is filled in by resolve. With HDR on, draws go to the hdr_ buffers instead
+// and data is filled in by tone_map. Colors are linear while shading and blending and are
+// converted as encoding says when they are stored in the 8-bit buffers, so data comes out sRGB
+// encoded by default, as displays and image files expect.
 pub struct Buffer {
     pub width: u32,
     pub height: u32,
     pub data: Vec<Pixel>,
     pub depth: Vec<f32>,
+    pub sample_data: Vec<Pixel>,
+    pub hdr_data: Vec<Vec4>,
+    pub hdr_sample_data: Vec<Vec4>,
+    multisample: Multisample,
+    hdr: bool,
+    // Samples were drawn to since the last resolve, so data or hdr_data are behind.
+    unresolved: bool,
     pub proj: Mat4,
     pub world: Mat4,
     pub obj: Mat4,
     pub obj2proj: Mat4,
     pub obj2world: Mat4,
+    // Draws tiles on all cores, the output is the same either way.
+    pub parallel: bool,
+    pub cull_mode: CullMode,
+    pub front_face: FrontFace,
+    // Skips meshes whose bounds lie outside the view volume before any vertex is shaded.
+    pub frustum_culling: bool,
+    pub stats: CullStats,
+    pub blend: BlendMode,
+    // Less for the standard depth range, Greater when reversed, see depth::DepthRange.
+    pub depth_compare: DepthCompare,
+    // Usually turned off while drawing blended geometry, so it does not hide what is behind it.
+    pub depth_write: bool,
+    pub depth_bias: DepthBias,
+    pub encoding: Encoding,
 }
 
 impl Buffer {
     pub fn new(_width: u32, _height: u32, _proj: Mat4, _world: Mat4) -> Buffer {
+        let size = (_width * _height) as usize;
         Buffer {
             width: _width,
             height: _height,
-            data: Vec::new(),
-            depth: Vec::new(),
+            data: vec![0; size],
+            depth: vec![f32::INFINITY; size],
+            sample_data: Vec::new(),
+            hdr_data: Vec::new(),
+            hdr_sample_data: Vec::new(),
+            multisample: Multisample::Off,
+            hdr: false,
+            unresolved: false,
             proj: _proj,
             world: _world,
             obj: Mat4::identity(),
             obj2proj: Mat4::identity(),
             obj2world: Mat4::identity(),
+            parallel: true,
+            cull_mode: CullMode::Back,
+            front_face: FrontFace::Clockwise,
+            frustum_culling: true,
+            stats: CullStats::default(),
+            blend: BlendMode::Replace,
+            depth_compare: DepthCompare::Less,
+            depth_write: true,
+            depth_bias: DepthBias::default(),
+            encoding: Encoding::new(ColorSpace::Srgb),
         }
     }
 
@@ -54,201 +162,436 @@ impl Buffer {
         self.obj = Mat4::identity();
     }
 
+    // Only reallocates when growing past the largest size so far. The contents are
+    // undefined until the next clear.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        let size = (width * height) as usize;
+        self.width = width;
+        self.height = height;
+        self.data.resize(size, 0);
+        self.resize_targets();
+    }
+
+    pub fn multisample(&self) -> Multisample {
+        self.multisample
+    }
+
+    // Like resize, the contents are undefined until the next clear.
+    pub fn set_multisample(&mut self, multisample: Multisample) {
+        self.multisample = multisample;
+        self.resize_targets();
+    }
+
+    pub fn hdr(&self) -> bool {
+        self.hdr
+    }
+
+    // Renders to linear f32 colors that may go past 1. Like resize, the contents are undefined
+    // until the next clear.
+    pub fn set_hdr(&mut self, hdr: bool) {
+        self.hdr = hdr;
+        self.resize_targets();
+    }
+
+    // Everything but data, which has the same size whatever the settings.
+    fn resize_targets(&mut self) {
+        let pixels = (self.width * self.height) as usize;
+        let count = self.multisample.count();
+        let size = pixels * count;
+        let samples = if count > 1 { size } else { 0 };
+        let (ldr, hdr) = if self.hdr { (0, 1) } else { (1, 0) };
+        self.unresolved = false;
+        self.depth.resize(size, f32::INFINITY);
+        self.sample_data.resize(samples * ldr, 0);
+        self.hdr_data.resize(pixels * hdr, Vec4::zero());
+        self.hdr_sample_data.resize(samples * hdr, Vec4::zero());
+    }
+
+    // The color is taken as sRGB, like the ones given to draw_triangle.
     pub fn clear_color(&mut self, c: Color) {
-        self.data.clear();
+        let (r, g, b, a) = c.to_linear();
+        let linear = Vec4::new(r, g, b, a);
+        // Not dithered, a flat color has no bands to hide
+        let color = Encoding::new(self.encoding.space).encode(linear, 0, 0);
+        self.data.fill(color);
+        self.sample_data.fill(color);
+
+        self.hdr_data.fill(linear);
+        self.hdr_sample_data.fill(linear);
+        self.unresolved = false;
+    }
+
+    // Averages the samples of every pixel into data, or hdr_data with HDR on. Nothing to do
+    // without multisampling. Has to be called after drawing before the output is read, which
+    // pixels, data_as_u8_vec and the savers check in debug builds.
+    pub fn resolve(&mut self) {
+        let count = self.multisample.count();
+        if count == 1 {
+            return;
+        }
+        self.unresolved = false;
 
-        for _ in 0..self.width {
-            for _ in 0..self.height {
-                self.data.push(Pixel::new(c.r, c.g, c.b));
+        if self.hdr {
+            let samples = self.hdr_sample_data.chunks(count);
+            for (pixel, samples) in self.hdr_data.iter_mut().zip(samples) {
+                let sum = samples.iter().fold(Vec4::zero(), |sum, s| sum + *s);
+                *pixel = sum / count as f32;
             }
+            return;
+        }
+
+        // Averaged in linear space, sRGB values would make edges too dark
+        let (encoding, width) = (self.encoding, self.width as usize);
+        let samples = self.sample_data.chunks(count);
+        for (i, (pixel, samples)) in self.data.iter_mut().zip(samples).enumerate() {
+            let sum = samples
+                .iter()
+                .fold(Vec4::zero(), |sum, s| sum + encoding.decode(*s));
+            let (x, y) = ((i % width) as u32, (i / width) as u32);
+            *pixel = encoding.encode(sum / count as f32, x, y);
         }
     }
 
     pub fn clear_depth(&mut self, value: f32) {
-        self.depth.clear();
+        self.depth.fill(value);
+    }
 
-        for _ in 0..self.width {
-            for _ in 0..self.height {
-                self.depth.push(value);
-            }
+    // Converts hdr_data into data, resolving first if needed. Nothing to do with HDR off.
+    pub fn tone_map(&mut self, mapping: &ToneMapping) {
+        if !self.hdr {
+            return;
+        }
+        if self.unresolved {
+            self.resolve();
+        }
+        let width = self.width as usize;
+        for (i, (pixel, color)) in self.data.iter_mut().zip(self.hdr_data.iter()).enumerate() {
+            let (x, y) = ((i % width) as u32, (i / width) as u32);
+            *pixel = self.encoding.encode(mapping.apply(*color), x, y);
         }
     }
 
-    fn tr(&mut self, vec: Vec4) -> Vec4 {
-        let result: Vec4 = vec.mul_matrix_left(&self.obj2proj);
-        [
-            result[0] / result[3],
-            result[1] / result[3],
-            result[2] / result[3],
-            1.,
-        ]
+    // Linear HDR output, fails without creating the file when HDR is off.
+    pub fn save_to_exr(&self, path: &str) -> io::Result<()> {
+        self.check_hdr()?;
+        self.assert_resolved();
+        let mut file = BufWriter::new(File::create(path)?);
+        hdr::write_exr(&mut file, self.width, self.height, &self.hdr_data)
     }
 
-    pub fn translate(&mut self, vec: Vec3) {
-        let mut m: Mat4 = Mat4::identity();
-        m[3] = vec[0];
-        m[7] = vec[1];
-        m[11] = vec[2];
-        self.obj = m.mul(&self.obj);
+    // Linear HDR output as Radiance RGBE, fails like save_to_exr.
+    pub fn save_to_hdr(&self, path: &str) -> io::Result<()> {
+        self.check_hdr()?;
+        self.assert_resolved();
+        let mut file = BufWriter::new(File::create(path)?);
+        hdr::write_radiance(&mut file, self.width, self.height, &self.hdr_data)
     }
 
-    pub fn scale(&mut self, vec: Vec3) {
-        let mut m: Mat4 = Mat4::identity();
-        m[0] = vec[0];
-        m[5] = vec[1];
-        m[10] = vec[2];
+    fn check_hdr(&self) -> io::Result<()> {
+        if self.hdr {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "HDR output needs the buffer rendered with HDR on",
+            ))
+        }
+    }
 
-        self.obj = m.mul(&self.obj);
+    // The color buffer as packed ARGB, ready for minifb. Call resolve first when multisampling.
+    pub fn pixels(&self) -> &[u32] {
+        self.assert_resolved();
+        &self.data
     }
 
-    pub fn rotate(&mut self, a: f32, vec: Vec3) {
-        let s = f32::sin(a * std::f32::consts::PI / 180.);
-        let c = f32::cos(a * std::f32::consts::PI / 180.);
-        vec.normalize(vec);
-
-        let mut m: Mat4 = [
-            vec[0] * vec[0] * (1. - c) + c,
-            vec[1] * vec[0] * (1. - c) + vec[2] * s,
-            vec[0] * vec[2] * (1. - c) - vec[1] * s,
-            0.,
-            vec[0] * vec[1] * (1. - c) - vec[2] * s,
-            vec[1] * vec[1] * (1. - c) + c,
-            vec[1] * vec[2] * (1. - c) + vec[0] * s,
-            0.,
-            vec[2] * vec[2] * (1. - c) + vec[1] * s,
-            vec[1] * vec[2] * (1. - c) - vec[0] * s,
-            vec[2] * vec[2] * (1. - c) + c,
-            0.,
-            0.,
-            0.,
-            0.,
-            1.,
-        ];
-
-        self.obj = m.mul(&self.obj);
-    }
-
-    pub fn draw_triangle(
-        &mut self,
-        va: Vec3,
-        vb: Vec3,
-        vc: Vec3,
-        mut c1: Color,
-        mut c2: Color,
-        mut c3: Color,
-    ) {
-        self.obj2world = self.world.mul(&self.obj);
-        self.obj2proj = self.proj.mul(&self.obj2world);
+    fn assert_resolved(&self) {
+        debug_assert!(
+            !self.unresolved,
+            "multisampled draws are only visible after Buffer::resolve"
+        );
+    }
 
-        let mut veca: Vec4 = self.tr([va[0], va[1], va[2], 1.0]);
-        let mut vecb: Vec4 = self.tr([vb[0], vb[1], vb[2], 1.0]);
-        let mut vecc: Vec4 = self.tr([vc[0], vc[1], vc[2], 1.0]);
+    pub fn reset_stats(&mut self) {
+        self.stats = CullStats::default();
+    }
 
-        veca = veca.scale(1. / veca[3]);
-        vecb = vecb.scale(1. / vecb[3]);
-        vecc = vecc.scale(1. / vecc[3]);
+    // True when the mesh can be skipped under the current matrices, counting it if so.
+    pub fn cull_mesh(&mut self, mesh: &Mesh) -> bool {
+        if !self.frustum_culling {
+            return false;
+        }
+        self.update_transforms();
+        let culled = match mesh.bounds() {
+            Some(bounds) => cull::outside_frustum(&bounds, &self.obj2proj),
+            None => true,
+        };
+        if culled {
+            self.stats.triangles += mesh.t_size as usize;
+            self.stats.meshes_culled += 1;
+            self.stats.frustum_culled += mesh.t_size as usize;
+        }
+        culled
+    }
 
-        let maxx = clamp(f32::max(veca[0], vecb[0]).max(vecc[0]), -1., 1.);
-        let maxy = clamp(f32::max(veca[1], vecb[1]).max(vecc[1]), -1., 1.);
-        let minx = clamp(f32::min(veca[0], vecb[0]).min(vecc[0]) as f32, -1., 1.);
-        let miny = clamp(f32::min(veca[1], vecb[1]).min(vecc[1]) as f32, -1., 1.);
+    // Keeps 1/w in the last component so attributes can be interpolated perspective-correctly.
+    fn perspective_divide(vec: Vec4) -> Vec4 {
+        let inv_w = 1. / vec[3];
+        (vec.xyz() * inv_w).extend(inv_w)
+    }
 
-        let half_width = self.width as f32 * 0.5;
-        let half_height = self.height as f32 * 0.5;
+    pub fn translate(&mut self, vec: Vec3) {
+        let mut m: Mat4 = Mat4::identity();
+        m[3] = vec.x;
+        m[7] = vec.y;
+        m[11] = vec.z;
+        self.obj = m * self.obj;
+    }
 
-        let min_width = ((minx + 1.) * half_width) as u32;
-        let max_width = ((maxx + 1.) * half_width) as u32;
-        let min_height = ((miny + 1.) * half_height) as u32;
-        let max_height = ((maxy + 1.) * half_height) as u32;
+    pub fn scale(&mut self, vec: Vec3) {
+        let mut m: Mat4 = Mat4::identity();
+        m[0] = vec.x;
+        m[5] = vec.y;
+        m[10] = vec.z;
 
-        let dxab = veca[0] - vecb[0];
-        let dxbc = vecb[0] - vecc[0];
-        let dxca = vecc[0] - veca[0];
-        let dyab = veca[1] - vecb[1];
-        let dybc = vecb[1] - vecc[1];
-        let dyca = vecc[1] - veca[1];
+        self.obj = m * self.obj;
+    }
 
-        let c1_n = c1.normalize();
-        let c2_n = c2.normalize();
-        let c3_n = c3.normalize();
+    pub fn transform(&mut self, m: Mat4) {
+        self.obj = m * self.obj;
+    }
 
-        let l1d = 1. / (-dybc * dxca + dxbc * dyca);
-        let l2d = 1. / (dyca * dxbc - dxca * dybc);
+    // Angle in degrees, clockwise when seen from the tip of the axis. That is the opposite of
+    // Quat::from_axis_angle, kept from the matrix this used to build.
+    pub fn rotate(&mut self, a: f32, vec: Vec3) {
+        let q = Quat::from_axis_angle(vec, -a * std::f32::consts::PI / 180.);
+        self.transform(q.to_mat4());
+    }
 
-        let topleft1 = dyab < 0. || (dyab == 0. && dxab > 0.);
-        let topleft2 = dybc < 0. || (dybc == 0. && dxbc > 0.);
-        let topleft3 = dyca < 0. || (dyca == 0. && dxca > 0.);
+    fn update_transforms(&mut self) -> Transforms {
+        self.obj2world = self.world * self.obj;
+        self.obj2proj = self.proj * self.obj2world;
+
+        Transforms {
+            proj: self.proj,
+            world: self.world,
+            obj: self.obj,
+            obj2world: self.obj2world,
+            obj2proj: self.obj2proj,
+            normal: self
+                .obj2world
+                .inverse_transpose()
+                .unwrap_or_else(|| self.obj2world.linear()),
+        }
+    }
 
-        for h in min_width..max_width + 1{
-            let x: f32 = ((h as f32) / half_width) - 1.;
-            for w in min_height..max_height + 1{
-                let y: f32 = ((w as f32) / half_height) - 1.;
+    // Every call is a pass over the tiles of its own, use draw_triangles for more than a few.
+    pub fn draw_triangle(&mut self, va: Vec3, vb: Vec3, vc: Vec3, c1: Color, c2: Color, c3: Color) {
+        self.draw_triangles(std::iter::once([(va, c1), (vb, c2), (vc, c3)]));
+    }
 
-                let l1 = (dybc * (x - vecc[0]) - dxbc * (y - vecc[1])) * l1d;
-                let l2 = (dyca * (x - vecc[0]) - dxca * (y - vecc[1])) * l2d;
+    // Corners with their colors, all rasterized in one pass.
+    pub fn draw_triangles<I>(&mut self, triangles: I)
+    where
+        I: IntoIterator<Item = [(Vec3, Color); 3]>,
+    {
+        self.update_transforms();
+
+        let mut screen = Vec::new();
+        for corners in triangles {
+            let triangle = corners.map(|(position, color)| {
+                // Interpolated in linear space
+                let (r, g, b, a) = color.to_linear();
+                ClipVertex {
+                    position: self.obj2proj * position.extend(1.0),
+                    varying: [r, g, b, a],
+                }
+            });
+            self.setup_triangle(triangle, &mut screen);
+        }
+        self.rasterize(&screen, &VertexColor, &());
+    }
 
-                let l3: f32 = 1.0 - l1 - l2;
+    pub fn draw_triangle_with<U, VS, FS>(
+        &mut self,
+        vertices: [&Vertex; 3],
+        vs: &VS,
+        fs: &FS,
+        uniforms: &U,
+    ) where
+        VS: VertexShader<Uniforms = U>,
+        FS: FragmentShader<Uniforms = U, Varying = VS::Varying>,
+    {
+        let transforms = self.update_transforms();
+
+        let mut triangle = Vec::with_capacity(3);
+        for vertex in vertices.iter() {
+            let (position, varying) = vs.vertex(vertex, &transforms, uniforms);
+            triangle.push(ClipVertex { position, varying });
+        }
 
-                let rc: f32 = l1 * c1_n.0 + l2 * c2_n.0 + l3 * c3_n.0;
-                let gc: f32 = l1 * c1_n.1 + l2 * c2_n.1 + l3 * c3_n.1;
-                let bc: f32 = l1 * c1_n.2 + l2 * c2_n.2 + l3 * c3_n.2;
+        let mut screen = Vec::new();
+        self.setup_triangle([triangle[0], triangle[1], triangle[2]], &mut screen);
+        self.rasterize(&screen, fs, uniforms);
+    }
 
-                let mut base = h + self.width * w;
-                if base > self.width * self.height - 1 {
-                    base = self.width * self.height - 1;
-                }
+    // Runs the vertex shader once per vertex of the mesh and then draws every indexed triangle.
+    pub fn draw_mesh<U, VS, FS>(&mut self, mesh: &Mesh, vs: &VS, fs: &FS, uniforms: &U)
+    where
+        VS: VertexShader<Uniforms = U>,
+        FS: FragmentShader<Uniforms = U, Varying = VS::Varying>,
+    {
+        if self.cull_mesh(mesh) {
+            return;
+        }
+        let transforms = self.update_transforms();
+
+        let shaded: Vec<ClipVertex<VS::Varying>> = mesh
+            .vertices
+            .iter()
+            .map(|vertex| {
+                let (position, varying) = vs.vertex(vertex, &transforms, uniforms);
+                ClipVertex { position, varying }
+            })
+            .collect();
+
+        let mut triangles = Vec::with_capacity(mesh.t_size as usize);
+        for i in 0..mesh.t_size as usize {
+            let [a, b, c] = mesh.indices[i];
+            self.setup_triangle(
+                [shaded[a as usize], shaded[b as usize], shaded[c as usize]],
+                &mut triangles,
+            );
+        }
+        self.rasterize(&triangles, fs, uniforms);
+    }
 
-                let depth = l1 * veca[2] as f32 + l2 * vecb[2] as f32 + l3 * vecc[2] as f32;
-
-                let inside = ((((dxab) * (y as f32 - veca[1])) - ((dyab) * (x as f32 - veca[0]))
-                    > 0.)
-                    && !topleft1
-                    || (((dxab) * (y as f32 - veca[1])) - ((dyab) * (x as f32 - veca[0])) >= 0.)
-                        && topleft1)
-                    && ((((dxbc) * (y as f32 - vecb[1])) - ((dybc) * (x as f32 - vecb[0])) > 0.)
-                        && !topleft2
-                        || (((dxbc) * (y as f32 - vecb[1])) - ((dybc) * (x as f32 - vecb[0]))
-                            >= 0.)
-                            && topleft2)
-                    && ((((dxca) * (y as f32 - vecc[1])) - ((dyca) * (x as f32 - vecc[0])) > 0.)
-                        && !topleft3
-                        || (((dxca) * (y as f32 - vecc[1])) - ((dyca) * (x as f32 - vecc[0]))
-                            >= 0.)
-                            && topleft3);
-
-                let on_top = depth < self.depth[base as usize];
-
-                if inside && on_top {
-                    self.data[base as usize] =
-                        Pixel::new((rc * 255.0) as u8, (gc * 255.0) as u8, (bc * 255.0) as u8);
-                    self.depth[base as usize] = depth;
-                }
-            }
+    // Lights are given in the space the world matrix maps from and are moved to eye space here.
+    pub fn draw_mesh_lit(
+        &mut self,
+        mesh: &Mesh,
+        lighting: &Lighting,
+        material: &Material,
+        mode: ShadingMode,
+    ) {
+        let uniforms = LightingUniforms {
+            lighting: lighting.transform(&self.world),
+            material: *material,
+        };
+
+        match mode {
+            ShadingMode::Gouraud => self.draw_mesh(mesh, &GouraudShader, &GouraudShader, &uniforms),
+            ShadingMode::Phong => self.draw_mesh(mesh, &PhongShader, &PhongShader, &uniforms),
+        }
+    }
+
+    // Culls, clips and queues what is left of the triangle for rasterization. Facing is
+    // decided before clipping, the pieces keep the winding of the whole.
+    fn setup_triangle<V: Varying>(
+        &mut self,
+        triangle: [ClipVertex<V>; 3],
+        out: &mut Vec<ScreenTriangle<V>>,
+    ) {
+        self.stats.triangles += 1;
+        let [a, b, c] = triangle.map(|v| v.position);
+        let area = cull::clockwise_area(a, b, c);
+        if cull::culls(self.cull_mode, self.front_face, area) {
+            self.stats.face_culled += 1;
+            return;
+        }
+
+        let polygon = clip::clip_polygon(triangle.to_vec());
+        if polygon.is_empty() {
+            self.stats.clipped += 1;
+        }
+        for [a, b, c] in clip::triangulate(&polygon).iter() {
+            out.push(ScreenTriangle {
+                positions: [
+                    Buffer::perspective_divide(a.position),
+                    Buffer::perspective_divide(b.position),
+                    Buffer::perspective_divide(c.position),
+                ],
+                varyings: [a.varying, b.varying, c.varying],
+            });
+        }
+    }
+
+    // Sorts the triangles into screen tiles and draws those, see raster.rs.
+    fn rasterize<FS: FragmentShader>(
+        &mut self,
+        triangles: &[ScreenTriangle<FS::Varying>],
+        fs: &FS,
+        uniforms: &FS::Uniforms,
+    ) {
+        let (width, height, multisample) = (self.width, self.height, self.multisample);
+        let output = OutputState {
+            multisample,
+            blend: self.blend,
+            depth_compare: self.depth_compare,
+            depth_write: self.depth_write,
+            depth_bias: self.depth_bias,
+            encoding: self.encoding,
+        };
+        let multisampled = multisample != Multisample::Off;
+        self.unresolved |= multisampled;
+
+        if self.hdr {
+            let color = if multisampled {
+                &mut self.hdr_sample_data
+            } else {
+                &mut self.hdr_data
+            };
+            let target = Target {
+                width,
+                height,
+                color,
+                depth: &mut self.depth,
+                output,
+                parallel: self.parallel,
+            };
+            raster::draw(target, triangles, fs, uniforms);
+        } else {
+            let color = if multisampled {
+                &mut self.sample_data
+            } else {
+                &mut self.data
+            };
+            let target = Target {
+                width,
+                height,
+                color,
+                depth: &mut self.depth,
+                output,
+                parallel: self.parallel,
+            };
+            raster::draw(target, triangles, fs, uniforms);
         }
     }
 
-    fn data_as_u8_vec(&self) -> Vec<u8> {
-        let mut u8_vec = Vec::<u8>::new();
-        for el in &self.data {
-            u8_vec.push(el.color.r);
-            u8_vec.push(el.color.g);
-            u8_vec.push(el.color.b);
+    // Both give the bytes as stored, in the color space of encoding.
+    pub fn data_as_u8_vec(&self) -> Vec<u8> {
+        self.assert_resolved();
+        let mut u8_vec = Vec::<u8>::with_capacity(3 * self.data.len());
+        for pixel in &self.data {
+            let (r, g, b, _) = pixel::unpack(*pixel);
+            u8_vec.extend_from_slice(&[r, g, b]);
         }
         u8_vec
     }
 
-    pub fn data_as_u32_vec(&self) -> Vec<u32> {
-        let mut u32_vec = Vec::<u32>::new();
-        for el in &self.data {
-            let (r, g, b) = (el.color.r as u32, el.color.g as u32, el.color.b as u32);
-            u32_vec.push((r << 16) | (g << 8) | b); //(r << 16) | (g << 8) | b
+    pub fn data_as_rgba_vec(&self) -> Vec<u8> {
+        self.assert_resolved();
+        let mut u8_vec = Vec::<u8>::with_capacity(4 * self.data.len());
+        for pixel in &self.data {
+            let (r, g, b, a) = pixel::unpack(*pixel);
+            u8_vec.extend_from_slice(&[r, g, b, a]);
         }
-        u32_vec
+        u8_vec
     }
 }
 
+#[cfg(feature = "image-io")]
 impl Savable for Buffer {
-    fn save_to_png(&self, path: &str) {
+    fn save_to_png(&self, path: &str) -> Result<(), image::ImageError> {
         image::save_buffer(
             path,
             self.data_as_u8_vec().as_slice(),
@@ -256,7 +599,16 @@ impl Savable for Buffer {
             self.height,
             image::ColorType::Rgb8,
         )
-        .unwrap();
+    }
+
+    fn save_to_png_rgba(&self, path: &str) -> Result<(), image::ImageError> {
+        image::save_buffer(
+            path,
+            self.data_as_rgba_vec().as_slice(),
+            self.width,
+            self.height,
+            image::ColorType::Rgba8,
+        )
     }
 }
 
@@ -269,3 +621,163 @@ pub fn clamp<T: PartialOrd>(input: T, min: T, max: T) -> T {
         input
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::camera::Camera;
+    use super::light::Light;
+    use super::mesh::Render;
+    use super::mesh::Sphere;
+    use super::mesh::Torus;
+    use super::*;
+
+    // Where the object matrix puts a point.
+    fn moved(buf: &Buffer, x: f32, y: f32, z: f32) -> Vec3 {
+        (buf.obj * Vec4::new(x, y, z, 1.)).xyz()
+    }
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn rotate_turns_clockwise() {
+        let mut buf = Buffer::new(1, 1, Mat4::identity(), Mat4::identity());
+        buf.rotate(90., Vec3::new(0., 0., 1.));
+        assert_close(moved(&buf, 1., 0., 0.), Vec3::new(0., -1., 0.));
+        assert_close(moved(&buf, 0., 1., 0.), Vec3::new(1., 0., 0.));
+
+        buf.clear_object_matrices();
+        buf.rotate(90., Vec3::new(1., 0., 0.));
+        assert_close(moved(&buf, 0., 1., 0.), Vec3::new(0., 0., -1.));
+    }
+
+    fn background() -> Color {
+        Color {
+            r: 20,
+            g: 40,
+            b: 60,
+            a: 255,
+        }
+    }
+
+    fn buffer(multisample: Multisample, parallel: bool) -> Buffer {
+        let mut camera = Camera::new(45., 4. / 3., 0.1, 100.);
+        camera.look_at(Vec3::new(1., 2., 5.), Vec3::zero(), Vec3::new(0., 1., 0.));
+        let mut buf = Buffer::new(320, 240, camera.projection(), camera.view());
+        buf.set_multisample(multisample);
+        buf.parallel = parallel;
+        buf.clear_color(background());
+        buf.clear_depth(f32::INFINITY);
+        buf
+    }
+
+    // Lit, vertex colored and blended geometry overlapping across many tiles.
+    fn draw_scene(buf: &mut Buffer) {
+        let mut lighting = Lighting::new(Vec3::splat(0.1));
+        lighting.lights.push(Light::Directional {
+            direction: Vec3::new(-1., -1., -1.),
+            color: Vec3::splat(1.),
+        });
+        let mut torus = Mesh::construct();
+        <Mesh as Torus>::new(&mut torus, 32, 24, 1.2, 0.4);
+        let mut sphere = Mesh::construct();
+        <Mesh as Sphere>::new(&mut sphere, 24, 16);
+
+        let material = Material::new(Vec3::new(0.8, 0.3, 0.2));
+        buf.draw_mesh_lit(&torus, &lighting, &material, ShadingMode::Phong);
+        buf.translate(Vec3::new(0.5, 0., 0.));
+        sphere.render(buf);
+
+        buf.blend = BlendMode::Alpha;
+        buf.depth_write = false;
+        let mut glass = Material::new(Vec3::new(0.2, 0.4, 0.9));
+        glass.alpha = 0.5;
+        buf.scale(Vec3::splat(1.5));
+        buf.draw_mesh_lit(&sphere, &lighting, &glass, ShadingMode::Gouraud);
+        buf.resolve();
+    }
+
+    #[test]
+    fn parallel_output_matches_serial() {
+        for multisample in [Multisample::Off, Multisample::X4] {
+            let mut serial = buffer(multisample, false);
+            let mut parallel = buffer(multisample, true);
+            draw_scene(&mut serial);
+            draw_scene(&mut parallel);
+
+            assert!(serial.data.iter().any(|p| *p != serial.data[0]));
+            assert!(serial.data == parallel.data);
+            assert!(serial.sample_data == parallel.sample_data);
+            let bits = |buf: &Buffer| buf.depth.iter().map(|d| d.to_bits()).collect::<Vec<_>>();
+            assert!(bits(&serial) == bits(&parallel));
+        }
+    }
+
+    #[test]
+    fn batched_triangles_match_single_ones() {
+        let mut sphere = Mesh::construct();
+        <Mesh as Sphere>::new(&mut sphere, 24, 16);
+
+        let mut batched = buffer(Multisample::Off, true);
+        sphere.render(&mut batched);
+
+        let mut single = buffer(Multisample::Off, true);
+        for [a, b, c] in sphere.indices.iter() {
+            let color = |r, g, b| Color { r, g, b, a: 255 };
+            single.draw_triangle(
+                sphere.vertices[*a as usize].position,
+                sphere.vertices[*b as usize].position,
+                sphere.vertices[*c as usize].position,
+                color(255, 0, 0),
+                color(0, 255, 0),
+                color(0, 0, 255),
+            );
+        }
+        assert!(batched.data == single.data);
+        assert!(batched.depth == single.depth);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "resolve")]
+    fn unresolved_samples_are_not_read() {
+        let mut buf = buffer(Multisample::X4, true);
+        let mut sphere = Mesh::construct();
+        <Mesh as Sphere>::new(&mut sphere, 24, 16);
+        sphere.render(&mut buf);
+        buf.pixels();
+    }
+
+    #[test]
+    fn tone_map_resolves_first() {
+        let mut sphere = Mesh::construct();
+        <Mesh as Sphere>::new(&mut sphere, 24, 16);
+        let mapping = ToneMapping::new(tonemap::ToneMap::Clamp);
+
+        let mut explicit = buffer(Multisample::X4, true);
+        explicit.set_hdr(true);
+        explicit.clear_color(background());
+        sphere.render(&mut explicit);
+        explicit.resolve();
+        explicit.tone_map(&mapping);
+
+        let mut lazy = buffer(Multisample::X4, true);
+        lazy.set_hdr(true);
+        lazy.clear_color(background());
+        sphere.render(&mut lazy);
+        lazy.tone_map(&mapping);
+        assert!(lazy.pixels() == explicit.pixels());
+    }
+
+    #[test]
+    fn hdr_output_needs_hdr_on() {
+        let buf = buffer(Multisample::Off, true);
+        let path = std::env::temp_dir().join("ruster_hdr_output_needs_hdr_on.exr");
+        let path = path.to_str().unwrap();
+        for result in [buf.save_to_exr(path), buf.save_to_hdr(path)] {
+            assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        }
+        assert!(!std::path::Path::new(path).exists());
+    }
+}
@@ -0,0 +1,529 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::buffer::light::Material;
+use crate::buffer::math::vec2::Vec2;
+use crate::buffer::math::vec3::Vec3;
+use crate::buffer::mesh::Mesh;
+use crate::buffer::mesh::NormalWeighting;
+use crate::buffer::mesh::Vertex;
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+    // Reading or parsing a material library referenced by the model failed
+    Mtl { path: String, error: Box<ObjError> },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io(e) => write!(f, "{}", e),
+            ObjError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            ObjError::Mtl { path, error } => write!(f, "{}: {}", path, error),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+impl From<io::Error> for ObjError {
+    fn from(e: io::Error) -> ObjError {
+        ObjError::Io(e)
+    }
+}
+
+fn parse_error(line: usize, message: String) -> ObjError {
+    ObjError::Parse { line, message }
+}
+
+#[derive(Clone, Debug)]
+pub struct ObjMaterial {
+    pub name: String,
+    pub ambient: Vec3,
+    pub diffuse: Vec3,
+    pub specular: Vec3,
+    pub shininess: f32,
+    pub dissolve: f32,
+    pub diffuse_map: Option<String>,
+}
+
+impl ObjMaterial {
+    fn new(name: &str) -> ObjMaterial {
+        ObjMaterial {
+            name: name.to_string(),
+            ambient: Vec3::splat(0.2),
+            diffuse: Vec3::splat(0.8),
+            specular: Vec3::zero(),
+            shininess: 1.,
+            dissolve: 1.,
+            diffuse_map: None,
+        }
+    }
+
+    pub fn material(&self) -> Material {
+        Material {
+            ambient: self.ambient,
+            diffuse: self.diffuse,
+            specular: self.specular,
+            shininess: self.shininess,
+            alpha: self.dissolve,
+        }
+    }
+}
+
+// One mesh per object/group and material used inside it.
+pub struct ObjObject {
+    pub name: String,
+    pub group: String,
+    pub material: Option<String>,
+    pub mesh: Mesh,
+}
+
+pub struct ObjModel {
+    pub objects: Vec<ObjObject>,
+    pub materials: HashMap<String, ObjMaterial>,
+    pub material_libs: Vec<String>,
+}
+
+fn parse_floats<'a, I>(args: I, line: usize, min: usize, max: usize) -> Result<Vec<f32>, ObjError>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let mut values = Vec::new();
+    for arg in args {
+        let value = arg
+            .parse::<f32>()
+            .map_err(|_| parse_error(line, format!("invalid number '{}'", arg)))?;
+        values.push(value);
+    }
+
+    if values.len() < min || values.len() > max {
+        return Err(parse_error(
+            line,
+            format!("expected {} to {} numbers, got {}", min, max, values.len()),
+        ));
+    }
+    Ok(values)
+}
+
+// Turns a 1-based or negative (relative to the end) OBJ index into a 0-based one.
+fn resolve_index(index: &str, count: usize, line: usize) -> Result<usize, ObjError> {
+    let i = index
+        .parse::<i64>()
+        .map_err(|_| parse_error(line, format!("invalid index '{}'", index)))?;
+
+    let resolved = if i > 0 {
+        i - 1
+    } else if i < 0 {
+        count as i64 + i
+    } else {
+        -1
+    };
+
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(parse_error(line, format!("index {} out of range", i)));
+    }
+    Ok(resolved as usize)
+}
+
+struct Builder {
+    name: String,
+    group: String,
+    material: Option<String>,
+    mesh: Mesh,
+    // Per vertex, whether the face gave it a normal
+    has_normal: Vec<bool>,
+    lookup: HashMap<(usize, Option<usize>, Option<usize>), u32>,
+}
+
+impl Builder {
+    fn new(name: &str, group: &str, material: Option<String>) -> Builder {
+        Builder {
+            name: name.to_string(),
+            group: group.to_string(),
+            material,
+            mesh: Mesh::construct(),
+            has_normal: Vec::new(),
+            lookup: HashMap::new(),
+        }
+    }
+
+    fn finish(mut self) -> ObjObject {
+        self.mesh.v_size = self.mesh.vertices.len() as u32;
+        self.mesh.t_size = self.mesh.indices.len() as u32;
+        // Only vertices of faces without normals get generated ones, the others keep theirs
+        if self.has_normal.contains(&false) {
+            let given: Vec<Vec3> = self.mesh.vertices.iter().map(|v| v.normal).collect();
+            self.mesh.compute_smooth_normals(NormalWeighting::Angle);
+            for (i, vertex) in self.mesh.vertices.iter_mut().enumerate() {
+                if self.has_normal[i] {
+                    vertex.normal = given[i];
+                }
+            }
+        }
+
+        ObjObject {
+            name: self.name,
+            group: self.group,
+            material: self.material,
+            mesh: self.mesh,
+        }
+    }
+}
+
+pub fn parse_obj(source: &str) -> Result<ObjModel, ObjError> {
+    let mut positions: Vec<Vec3> = Vec::new();
+    let mut uvs: Vec<Vec2> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+
+    let mut objects = Vec::new();
+    let mut material_libs = Vec::new();
+    let mut current = Builder::new("", "", None);
+
+    for (n, raw) in source.lines().enumerate() {
+        let line = n + 1;
+        let content = raw.split('#').next().unwrap_or("");
+        let mut args = content.split_whitespace();
+        let keyword = match args.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+
+        match keyword {
+            "v" => {
+                let v = parse_floats(args, line, 3, 4)?;
+                positions.push(Vec3::new(v[0], v[1], v[2]));
+            }
+            "vt" => {
+                let v = parse_floats(args, line, 1, 3)?;
+                // OBJ puts v = 0 at the bottom of the image, textures here have it at the top
+                uvs.push(Vec2::new(v[0], 1. - v.get(1).cloned().unwrap_or(0.)));
+            }
+            "vn" => {
+                let v = parse_floats(args, line, 3, 3)?;
+                normals.push(Vec3::new(v[0], v[1], v[2]));
+            }
+            "f" => {
+                let mut corners = Vec::new();
+                for arg in args {
+                    let mut parts = arg.split('/');
+                    let v = resolve_index(parts.next().unwrap_or(""), positions.len(), line)?;
+                    let vt = match parts.next() {
+                        Some(s) if !s.is_empty() => Some(resolve_index(s, uvs.len(), line)?),
+                        _ => None,
+                    };
+                    let vn = match parts.next() {
+                        Some(s) if !s.is_empty() => Some(resolve_index(s, normals.len(), line)?),
+                        _ => None,
+                    };
+
+                    let (mesh, has_normal) = (&mut current.mesh, &mut current.has_normal);
+                    let index = *current.lookup.entry((v, vt, vn)).or_insert_with(|| {
+                        mesh.vertices.push(Vertex {
+                            position: positions[v],
+                            normal: vn.map(|i| normals[i]).unwrap_or_default(),
+                            uv: vt.map(|i| uvs[i]).unwrap_or_default(),
+                        });
+                        has_normal.push(vn.is_some());
+                        mesh.vertices.len() as u32 - 1
+                    });
+                    corners.push(index);
+                }
+
+                if corners.len() < 3 {
+                    return Err(parse_error(
+                        line,
+                        "face with less than 3 vertices".to_string(),
+                    ));
+                }
+
+                // OBJ faces are counter-clockwise, meshes here are wound clockwise
+                let indices = &mut current.mesh.indices;
+                for i in 1..corners.len() - 1 {
+                    indices.push([corners[0], corners[i + 1], corners[i]]);
+                }
+            }
+            "o" | "g" | "usemtl" => {
+                let value = args.collect::<Vec<&str>>().join(" ");
+                let (mut name, mut group, mut material) = (
+                    current.name.clone(),
+                    current.group.clone(),
+                    current.material.clone(),
+                );
+                match keyword {
+                    "o" => {
+                        name = value;
+                        group = String::new();
+                    }
+                    "g" => group = value,
+                    _ => material = Some(value),
+                }
+
+                let next = Builder::new(&name, &group, material);
+                let previous = std::mem::replace(&mut current, next);
+                if !previous.mesh.indices.is_empty() {
+                    objects.push(previous.finish());
+                }
+            }
+            // Every argument is a library of its own, so file names cannot contain spaces
+            "mtllib" => material_libs.extend(args.map(|lib| lib.to_string())),
+            // Smoothing groups, lines, points and free-form geometry are ignored
+            _ => {}
+        }
+    }
+
+    if !current.mesh.indices.is_empty() {
+        objects.push(current.finish());
+    }
+
+    Ok(ObjModel {
+        objects,
+        materials: HashMap::new(),
+        material_libs,
+    })
+}
+
+pub fn parse_mtl(source: &str) -> Result<HashMap<String, ObjMaterial>, ObjError> {
+    let mut materials = HashMap::new();
+    let mut current: Option<ObjMaterial> = None;
+
+    for (n, raw) in source.lines().enumerate() {
+        let line = n + 1;
+        let content = raw.split('#').next().unwrap_or("");
+        let mut args = content.split_whitespace();
+        let keyword = match args.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+
+        if keyword == "newmtl" {
+            let name = args.collect::<Vec<&str>>().join(" ");
+            if let Some(material) = current.replace(ObjMaterial::new(&name)) {
+                materials.insert(material.name.clone(), material);
+            }
+            continue;
+        }
+
+        let material = current
+            .as_mut()
+            .ok_or_else(|| parse_error(line, format!("'{}' before newmtl", keyword)))?;
+
+        match keyword {
+            "Ka" | "Kd" | "Ks" => {
+                let v = parse_floats(args, line, 3, 3)?;
+                let color = Vec3::new(v[0], v[1], v[2]);
+                match keyword {
+                    "Ka" => material.ambient = color,
+                    "Kd" => material.diffuse = color,
+                    _ => material.specular = color,
+                }
+            }
+            "Ns" => material.shininess = parse_floats(args, line, 1, 1)?[0],
+            "d" => material.dissolve = parse_floats(args, line, 1, 1)?[0],
+            "Tr" => material.dissolve = 1. - parse_floats(args, line, 1, 1)?[0],
+            // Options in front of the file name are ignored
+            "map_Kd" => material.diffuse_map = args.last().map(|s| s.to_string()),
+            _ => {}
+        }
+    }
+
+    if let Some(material) = current {
+        materials.insert(material.name.clone(), material);
+    }
+    Ok(materials)
+}
+
+// Loads the model and every material library it references, relative to the OBJ file.
+pub fn load_obj(path: &str) -> Result<ObjModel, ObjError> {
+    let mut model = parse_obj(&fs::read_to_string(path)?)?;
+
+    let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+    for lib in model.material_libs.iter() {
+        let path = dir.join(lib);
+        let materials = fs::read_to_string(&path)
+            .map_err(ObjError::from)
+            .and_then(|source| parse_mtl(&source))
+            .map_err(|error| ObjError::Mtl {
+                path: path.display().to_string(),
+                error: Box::new(error),
+            })?;
+        model.materials.extend(materials);
+    }
+
+    Ok(model)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    fn error_line(source: &str) -> usize {
+        match parse_obj(source) {
+            Err(ObjError::Parse { line, .. }) => line,
+            Err(e) => panic!("unexpected error {}", e),
+            Ok(_) => panic!("parsed"),
+        }
+    }
+
+    const SQUARE: &str = "
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+";
+
+    fn triangles(model: &ObjModel) -> Vec<[Vec3; 3]> {
+        let mesh = &model.objects[0].mesh;
+        mesh.indices
+            .iter()
+            .map(|t| t.map(|i| mesh.vertices[i as usize].position))
+            .collect()
+    }
+
+    #[test]
+    fn relative_indices() {
+        let absolute = parse_obj(&format!("{}f 1 2 3\n", SQUARE)).unwrap();
+        let relative = parse_obj(&format!("{}f -4 -3 -2\n", SQUARE)).unwrap();
+        assert_eq!(triangles(&absolute), triangles(&relative));
+
+        // Relative to the vertices read so far, not to the whole file
+        let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\nv 5 5 5\n";
+        let model = parse_obj(source).unwrap();
+        assert_eq!(model.objects[0].mesh.vertices.len(), 3);
+        assert!(model.objects[0]
+            .mesh
+            .vertices
+            .iter()
+            .all(|v| v.position.x < 2.));
+    }
+
+    #[test]
+    fn fan_triangulation_flips_winding() {
+        let quad = parse_obj(&format!("{}f 1 2 3 4\n", SQUARE)).unwrap();
+        assert_eq!(quad.objects[0].mesh.indices, [[0, 2, 1], [0, 3, 2]]);
+
+        let source = format!("{}v -1 0.5 0\nf 1 2 3 4 5\n", SQUARE);
+        let pentagon = parse_obj(&source).unwrap();
+        assert_eq!(
+            pentagon.objects[0].mesh.indices,
+            [[0, 2, 1], [0, 3, 2], [0, 4, 3]]
+        );
+        assert_eq!(pentagon.objects[0].mesh.t_size, 3);
+    }
+
+    #[test]
+    fn normal_and_uv_indices() {
+        let source = format!(
+            "{}vt 0.25 0.75\nvn 0 0 -1\nf 1//1 2//1 3//1\nf 1/1 3/1 4/1\n",
+            SQUARE
+        );
+        let model = parse_obj(&source).unwrap();
+        let vertices = &model.objects[0].mesh.vertices;
+        assert_eq!(vertices.len(), 6);
+
+        // v//vn carries the normal and no uv
+        for v in vertices[..3].iter() {
+            assert_close(v.normal, Vec3::new(0., 0., -1.));
+            assert_eq!(v.uv, Vec2::zero());
+        }
+        // v/vt carries the uv with v flipped to the top of the image
+        for v in vertices[3..].iter() {
+            assert_eq!(v.uv, Vec2::new(0.25, 0.25));
+        }
+    }
+
+    #[test]
+    fn normals_are_only_generated_where_missing() {
+        let source = format!("{}vn 1 0 0\nf 1//1 2//1 3//1\nf 1 3 4\n", SQUARE);
+        let model = parse_obj(&source).unwrap();
+        let vertices = &model.objects[0].mesh.vertices;
+
+        for v in vertices[..3].iter() {
+            assert_close(v.normal, Vec3::new(1., 0., 0.));
+        }
+        // The face is counter-clockwise seen from +z, so that is where its normals point
+        for v in vertices[3..].iter() {
+            assert_close(v.normal, Vec3::new(0., 0., 1.));
+        }
+    }
+
+    #[test]
+    fn materials_split_objects() {
+        let source = format!(
+            "mtllib a.mtl b.mtl\n{}usemtl red\nf 1 2 3\nusemtl blue\nf 1 3 4\n",
+            SQUARE
+        );
+        let model = parse_obj(&source).unwrap();
+        assert_eq!(model.material_libs, ["a.mtl", "b.mtl"]);
+
+        let materials: Vec<Option<&str>> = model
+            .objects
+            .iter()
+            .map(|o| o.material.as_deref())
+            .collect();
+        assert_eq!(materials, [Some("red"), Some("blue")]);
+        assert!(model.objects.iter().all(|o| o.mesh.t_size == 1));
+    }
+
+    #[test]
+    fn error_lines() {
+        assert_eq!(error_line("v 0 0 0\nv 1 x 0\n"), 2);
+        assert_eq!(error_line("v 0 0\n"), 1);
+        assert_eq!(error_line(&format!("{}\nf 1 2 5\n", SQUARE)), 7);
+        assert_eq!(error_line(&format!("{}f 1 2 0\n", SQUARE)), 6);
+        assert_eq!(error_line(&format!("{}# comment\nf 1 2\n", SQUARE)), 7);
+        assert_eq!(error_line(&format!("{}f 1/1 2/1 3/1\n", SQUARE)), 6);
+    }
+
+    #[test]
+    fn mtl_materials() {
+        let source = "
+newmtl red
+Kd 1 0 0
+Ns 10
+d 0.5
+map_Kd -s 2 2 2 red.png
+
+newmtl clear # comment
+Tr 0.25
+";
+        let materials = parse_mtl(source).unwrap();
+        let red = &materials["red"];
+        assert_eq!(red.diffuse, Vec3::new(1., 0., 0.));
+        assert_eq!(red.shininess, 10.);
+        assert_eq!(red.dissolve, 0.5);
+        assert_eq!(red.diffuse_map.as_deref(), Some("red.png"));
+        assert_eq!(materials["clear"].dissolve, 0.75);
+
+        match parse_mtl("\nKd 1 1 1\n") {
+            Err(ObjError::Parse { line, .. }) => assert_eq!(line, 2),
+            _ => panic!("Kd before newmtl was accepted"),
+        }
+    }
+
+    #[test]
+    fn mtl_errors_name_the_library() {
+        let dir = std::env::temp_dir().join("ruster-obj-test");
+        fs::create_dir_all(&dir).unwrap();
+        let obj = dir.join("model.obj");
+        fs::write(&obj, format!("mtllib missing.mtl\n{}f 1 2 3\n", SQUARE)).unwrap();
+
+        match load_obj(obj.to_str().unwrap()) {
+            Err(ObjError::Mtl { path, error }) => {
+                assert!(path.ends_with("missing.mtl"));
+                assert!(matches!(*error, ObjError::Io(_)));
+            }
+            _ => panic!("missing library was not reported"),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
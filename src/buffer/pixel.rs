@@ -1,18 +1,130 @@
-use crate::Color;
+use crate::buffer::color::ColorSpace;
+use crate::buffer::math::vec4::Vec4;
 
-pub struct Pixel {
-    pub color: Color,
+// Pixels are packed into a u32 as 0xAARRGGBB. minifb takes them as they are and ignores the
+// alpha byte.
+pub type Pixel = u32;
+
+pub fn pack(r: u8, g: u8, b: u8, a: u8) -> Pixel {
+    ((a as u32) << 24) | ((r as u32) << 16) | ((g as u32) << 8) | b as u32
+}
+
+pub fn unpack(pixel: Pixel) -> (u8, u8, u8, u8) {
+    (
+        (pixel >> 16) as u8,
+        (pixel >> 8) as u8,
+        pixel as u8,
+        (pixel >> 24) as u8,
+    )
+}
+
+// 4x4 ordered dither thresholds, spread evenly over [0, 1).
+const BAYER: [[f32; 4]; 4] = [
+    [0.5 / 16., 8.5 / 16., 2.5 / 16., 10.5 / 16.],
+    [12.5 / 16., 4.5 / 16., 14.5 / 16., 6.5 / 16.],
+    [3.5 / 16., 11.5 / 16., 1.5 / 16., 9.5 / 16.],
+    [15.5 / 16., 7.5 / 16., 13.5 / 16., 5.5 / 16.],
+];
+
+// How linear colors are turned into pixels and back. Alpha is stored linearly either way.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Encoding {
+    pub space: ColorSpace,
+    // Rounds up or down following a fixed pattern instead of to the nearest value, which
+    // breaks up the banding of smooth gradients
+    pub dither: bool,
+}
+
+impl Encoding {
+    pub fn new(space: ColorSpace) -> Encoding {
+        Encoding {
+            space,
+            dither: false,
+        }
+    }
+
+    // Channels outside [0, 1] saturate. x and y pick the dither threshold.
+    pub fn encode(&self, c: Vec4, x: u32, y: u32) -> Pixel {
+        let threshold = if self.dither {
+            BAYER[(y & 3) as usize][(x & 3) as usize]
+        } else {
+            0.5
+        };
+        let quantize = |v: f32| (v * 255.0 + threshold) as u8;
+        let encode = |v: f32| quantize(self.space.from_linear(v.max(0.)));
+        pack(encode(c.x), encode(c.y), encode(c.z), quantize(c.w))
+    }
+
+    pub fn decode(&self, pixel: Pixel) -> Vec4 {
+        let (r, g, b, a) = unpack(pixel);
+        let decode = |c: u8| self.space.to_linear(c as f32 / 255.0);
+        Vec4::new(decode(r), decode(g), decode(b), a as f32 / 255.0)
+    }
 }
 
-impl Pixel {
-    pub fn default(&mut self) -> Pixel {
-        Pixel {
-            color: Color { r: 0, g: 0, b: 0 },
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packing() {
+        assert_eq!(pack(0x12, 0x34, 0x56, 0x78), 0x7812_3456);
+        assert_eq!(unpack(0x7812_3456), (0x12, 0x34, 0x56, 0x78));
+        for pixel in [
+            0,
+            0xffff_ffff,
+            0xff00_0000,
+            0x00ff_0000,
+            0x0000_ff00,
+            0x0000_00ff,
+        ] {
+            let (r, g, b, a) = unpack(pixel);
+            assert_eq!(pack(r, g, b, a), pixel);
         }
     }
-    pub fn new(r: u8, g: u8, b: u8) -> Pixel {
-        Pixel {
-            color: Color { r, g, b },
+
+    #[test]
+    fn every_byte_survives_decode_and_encode() {
+        for space in [ColorSpace::Linear, ColorSpace::Srgb] {
+            let encoding = Encoding::new(space);
+            for v in 0..=255u8 {
+                let pixel = pack(v, v, v, v);
+                assert_eq!(encoding.encode(encoding.decode(pixel), 0, 0), pixel);
+            }
+        }
+    }
+
+    #[test]
+    fn encode_rounds_and_saturates() {
+        let encoding = Encoding::new(ColorSpace::Linear);
+        let encode = |v: f32| unpack(encoding.encode(Vec4::new(v, v, v, v), 0, 0));
+        assert_eq!(encode(0.5), (128, 128, 128, 128));
+        assert_eq!(encode(-1.), (0, 0, 0, 0));
+        assert_eq!(encode(2.), (255, 255, 255, 255));
+
+        // Alpha is never gamma encoded
+        let srgb = Encoding::new(ColorSpace::Srgb);
+        let (r, _, _, a) = unpack(srgb.encode(Vec4::new(0.5, 0., 0., 0.5), 0, 0));
+        assert_eq!((r, a), (188, 128));
+    }
+
+    #[test]
+    fn dither_stays_within_one_step() {
+        let mut encoding = Encoding::new(ColorSpace::Linear);
+        encoding.dither = true;
+        for i in 0..=1000 {
+            let v = i as f32 / 1000.;
+            let exact = v * 255.;
+            let mut sum = 0.;
+            for y in 0..4 {
+                for x in 0..4 {
+                    let (r, ..) = unpack(encoding.encode(Vec4::new(v, v, v, 1.), x, y));
+                    assert!((r as f32 - exact).abs() < 1., "{} at {} {}", v, x, y);
+                    sum += r as f32;
+                }
+            }
+            // Over the whole pattern the rounding evens out
+            assert!((sum / 16. - exact).abs() <= 1. / 32. + 1e-3, "{}", v);
         }
     }
 }
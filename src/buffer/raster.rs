@@ -0,0 +1,811 @@
+use crate::buffer::blend;
+use crate::buffer::blend::BlendMode;
+use crate::buffer::depth::DepthBias;
+use crate::buffer::depth::DepthCompare;
+use crate::buffer::math::vec4::Vec4;
+use crate::buffer::pixel::Encoding;
+use crate::buffer::pixel::Pixel;
+use crate::buffer::shader::Fragment;
+use crate::buffer::shader::FragmentShader;
+use crate::buffer::shader::Varying;
+use crate::buffer::simd::F32x8;
+use crate::buffer::simd::I32x8;
+
+// Even, so the 2x2 quads never straddle two tiles, and a multiple of BLOCK_SIZE.
+pub const TILE_SIZE: u32 = 64;
+
+// Blocks are tested against the edges as a whole before looking at single pixels.
+const BLOCK_SIZE: u32 = 8;
+
+// Vertices are snapped to 1/16 of a pixel, so edges are exact and neighbouring triangles
+// share every pixel on their common edge with neither gaps nor overlaps.
+const SUBPIXEL_BITS: u32 = 4;
+
+// Pixels are evaluated 4x2 at a time, two quads side by side.
+const LANE_X: [i32; 8] = [0, 1, 2, 3, 0, 1, 2, 3];
+const LANE_Y: [i32; 8] = [0, 0, 0, 0, 1, 1, 1, 1];
+
+pub const MAX_SAMPLES: usize = 8;
+
+// Samples per pixel. Coverage and depth are kept per sample while the fragment shader still
+// runs once per pixel, at its center.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Multisample {
+    Off,
+    X2,
+    X4,
+    X8,
+}
+
+impl Multisample {
+    // None for counts other than 1, 2, 4 and 8.
+    pub fn from_count(count: u32) -> Option<Multisample> {
+        match count {
+            1 => Some(Multisample::Off),
+            2 => Some(Multisample::X2),
+            4 => Some(Multisample::X4),
+            8 => Some(Multisample::X8),
+            _ => None,
+        }
+    }
+
+    pub fn count(self) -> usize {
+        self.offsets().len()
+    }
+
+    // Sample positions relative to the pixel center in sub-pixel units. These are the rotated
+    // grids of Direct3D's standard patterns, mirrored since rows count upwards here.
+    fn offsets(self) -> &'static [(i64, i64)] {
+        match self {
+            Multisample::Off => &[(0, 0)],
+            Multisample::X2 => &[(4, -4), (-4, 4)],
+            Multisample::X4 => &[(-2, 6), (6, 2), (-6, -2), (2, -6)],
+            Multisample::X8 => &[
+                (1, 3),
+                (-1, -3),
+                (5, -1),
+                (-3, 5),
+                (-5, -5),
+                (-7, 1),
+                (3, -7),
+                (7, 7),
+            ],
+        }
+    }
+
+    // How far the samples reach from the pixel center along either axis.
+    fn reach(self) -> i64 {
+        self.offsets()
+            .iter()
+            .map(|(x, y)| x.abs().max(y.abs()))
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+// What the color buffer holds, packed 8-bit pixels or linear floats for HDR.
+pub trait ColorFormat: Copy + Send + Sync {
+    // From linear RGBA, x and y being the pixel it goes to.
+    fn store(color: Vec4, encoding: &Encoding, x: u32, y: u32) -> Self;
+
+    fn load(self, encoding: &Encoding) -> Vec4;
+}
+
+impl ColorFormat for Pixel {
+    fn store(color: Vec4, encoding: &Encoding, x: u32, y: u32) -> Pixel {
+        encoding.encode(color, x, y)
+    }
+
+    fn load(self, encoding: &Encoding) -> Vec4 {
+        encoding.decode(self)
+    }
+}
+
+// Kept as they are, above 1 included. Already linear, the encoding only applies once they are
+// tone mapped.
+impl ColorFormat for Vec4 {
+    fn store(color: Vec4, _: &Encoding, _: u32, _: u32) -> Vec4 {
+        color
+    }
+
+    fn load(self, _: &Encoding) -> Vec4 {
+        self
+    }
+}
+
+// How fragments end up in the framebuffer, fixed for the duration of a draw.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct OutputState {
+    pub multisample: Multisample,
+    pub blend: BlendMode,
+    pub depth_compare: DepthCompare,
+    pub depth_write: bool,
+    pub depth_bias: DepthBias,
+    pub encoding: Encoding,
+}
+
+// A triangle after the perspective divide: xy in NDC, z the depth and w holding 1/w.
+#[derive(Clone, Copy)]
+pub struct ScreenTriangle<V> {
+    pub positions: [Vec4; 3],
+    pub varyings: [V; 3],
+}
+
+impl<V> ScreenTriangle<V> {
+    // Vertex positions in sub-pixel units, rows counted from the bottom. Pixel (h, w) is
+    // sampled at (h, w) << SUBPIXEL_BITS.
+    fn fixed(&self, width: u32, height: u32) -> [(i64, i64); 3] {
+        let scale = (1 << SUBPIXEL_BITS) as f32;
+        let half_width = width as f32 * 0.5;
+        let half_height = height as f32 * 0.5;
+        let snap = |p: Vec4| {
+            (
+                ((p.x + 1.) * half_width * scale).round() as i64,
+                ((p.y + 1.) * half_height * scale).round() as i64,
+            )
+        };
+        [
+            snap(self.positions[0]),
+            snap(self.positions[1]),
+            snap(self.positions[2]),
+        ]
+    }
+
+    // The larger of the depth changes per pixel along x and y.
+    fn depth_slope(&self, width: u32, height: u32) -> f32 {
+        let [a, b, c] = self.positions;
+        let (e1, e2) = (b - a, c - a);
+        let (sx, sy) = (width as f32 / 2., height as f32 / 2.);
+        let (x1, y1, x2, y2) = (e1.x * sx, e1.y * sy, e2.x * sx, e2.y * sy);
+        let det = x1 * y2 - x2 * y1;
+        if det == 0. {
+            return 0.;
+        }
+        let dzdx = (e1.z * y2 - e2.z * y1) / det;
+        let dzdy = (x1 * e2.z - x2 * e1.z) / det;
+        dzdx.abs().max(dzdy.abs())
+    }
+
+    // Pixel columns and rows (counted from the bottom) the triangle can touch, inclusive and
+    // limited to the framebuffer. None if no sample falls inside the bounding box.
+    fn bounds(&self, width: u32, height: u32, reach: i64) -> Option<(u32, u32, u32, u32)> {
+        let [a, b, c] = self.fixed(width, height);
+        let (x_range, y_range) = ((0, width as i64 - 1), (0, height as i64 - 1));
+        bounds(a, b, c, reach, x_range, y_range)
+    }
+}
+
+impl<V: Copy> ScreenTriangle<V> {
+    // The same triangle wound the other way round.
+    fn flipped(&self) -> ScreenTriangle<V> {
+        let [a, b, c] = self.positions;
+        let [va, vb, vc] = self.varyings;
+        ScreenTriangle {
+            positions: [a, c, b],
+            varyings: [va, vc, vb],
+        }
+    }
+}
+
+// reach widens the box by how far samples lie from the pixel centers.
+fn bounds(
+    a: (i64, i64),
+    b: (i64, i64),
+    c: (i64, i64),
+    reach: i64,
+    (x0, x1): (i64, i64),
+    (y0, y1): (i64, i64),
+) -> Option<(u32, u32, u32, u32)> {
+    let round = (1 << SUBPIXEL_BITS) - 1;
+    // Shifts floor, so adding round first gives the ceiling
+    let min_x = i64::max((a.0.min(b.0).min(c.0) - reach + round) >> SUBPIXEL_BITS, x0);
+    let min_y = i64::max((a.1.min(b.1).min(c.1) - reach + round) >> SUBPIXEL_BITS, y0);
+    let max_x = i64::min((a.0.max(b.0).max(c.0) + reach) >> SUBPIXEL_BITS, x1);
+    let max_y = i64::min((a.1.max(b.1).max(c.1) + reach) >> SUBPIXEL_BITS, y1);
+    if min_x > max_x || min_y > max_y {
+        return None;
+    }
+    Some((min_x as u32, max_x as u32, min_y as u32, max_y as u32))
+}
+
+// The edge from p to q as a*x + b*y + c, positive on the inner side of triangles wound
+// clockwise on screen. Edges on the top or left of a triangle own the pixels
+// lying exactly on them, the others get a bias of -1 so that those pixels fail the >= 0 test.
+#[derive(Clone, Copy)]
+struct Edge {
+    a: i64,
+    b: i64,
+    c: i64,
+    bias: i64,
+}
+
+impl Edge {
+    fn new(p: (i64, i64), q: (i64, i64)) -> Edge {
+        let (dx, dy) = (p.0 - q.0, p.1 - q.1);
+        let top_left = dy < 0 || (dy == 0 && dx > 0);
+        Edge {
+            a: -dy,
+            b: dx,
+            c: dy * p.0 - dx * p.1,
+            bias: if top_left { 0 } else { -1 },
+        }
+    }
+
+    // At the center of pixel (h, w).
+    fn at(&self, h: u32, w: u32) -> i64 {
+        self.a * ((h as i64) << SUBPIXEL_BITS) + self.b * ((w as i64) << SUBPIXEL_BITS) + self.c
+    }
+
+    // Change from the pixel center to a sample offset from it.
+    fn shift(&self, (x, y): (i64, i64)) -> i64 {
+        self.a * x + self.b * y
+    }
+}
+
+// A 2x2 quad with its lower left pixel at (h, w), lanes numbered 0 1 / 2 3. Coverage has bit s
+// set for every sample s inside the triangle, depths are taken at the samples and the rest at
+// the pixel centers.
+struct Quad {
+    h: u32,
+    w: u32,
+    coverage: [u32; 4],
+    depths: [[f32; MAX_SAMPLES]; 4],
+    center_depths: [f32; 4],
+    weights: [(f32, f32, f32); 4],
+}
+
+// A rectangle of the framebuffer with the triangles overlapping it. Rows are counted from the
+// bottom like NDC y, so color[0] is the lowest row of the tile. Rows hold the samples of each
+// pixel next to each other.
+pub struct Tile<'a, C> {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    output: OutputState,
+    color: Vec<&'a mut [C]>,
+    depth: Vec<&'a mut [f32]>,
+    triangles: Vec<usize>,
+}
+
+// Cuts the color and depth buffers into tiles without copying, every tile borrows its own
+// part of each row. Both buffers hold one entry per sample.
+pub fn split<'a, C>(
+    width: u32,
+    height: u32,
+    output: OutputState,
+    color: &'a mut [C],
+    depth: &'a mut [f32],
+) -> Vec<Tile<'a, C>> {
+    let columns = width.div_ceil(TILE_SIZE);
+    let rows = height.div_ceil(TILE_SIZE);
+
+    let mut tiles = Vec::with_capacity((columns * rows) as usize);
+    for ty in 0..rows {
+        for tx in 0..columns {
+            let (x, y) = (tx * TILE_SIZE, ty * TILE_SIZE);
+            tiles.push(Tile {
+                x,
+                y,
+                width: u32::min(TILE_SIZE, width - x),
+                height: u32::min(TILE_SIZE, height - y),
+                output,
+                color: Vec::new(),
+                depth: Vec::new(),
+                triangles: Vec::new(),
+            });
+        }
+    }
+
+    // Rows are stored top to bottom
+    let row = width as usize * output.multisample.count();
+    let piece = TILE_SIZE as usize * output.multisample.count();
+    let stored_rows = color.chunks_mut(row).zip(depth.chunks_mut(row)).rev();
+    for (w, (color_row, depth_row)) in stored_rows.enumerate() {
+        let ty = w as u32 / TILE_SIZE;
+        let pieces = color_row.chunks_mut(piece).zip(depth_row.chunks_mut(piece));
+        for (tx, (c, d)) in pieces.enumerate() {
+            let tile = &mut tiles[(ty * columns) as usize + tx];
+            tile.color.push(c);
+            tile.depth.push(d);
+        }
+    }
+    tiles
+}
+
+// Hands every triangle to the tiles its bounding box overlaps, keeping submission order so
+// depth ties resolve the same way however the tiles are scheduled.
+pub fn bin<V, C>(
+    tiles: &mut [Tile<C>],
+    triangles: &[ScreenTriangle<V>],
+    width: u32,
+    height: u32,
+    multisample: Multisample,
+) {
+    let columns = width.div_ceil(TILE_SIZE);
+    let reach = multisample.reach();
+    for (i, triangle) in triangles.iter().enumerate() {
+        let (min_x, max_x, min_y, max_y) = match triangle.bounds(width, height, reach) {
+            Some(bounds) => bounds,
+            None => continue,
+        };
+        for ty in min_y / TILE_SIZE..=max_y / TILE_SIZE {
+            for tx in min_x / TILE_SIZE..=max_x / TILE_SIZE {
+                tiles[(ty * columns + tx) as usize].triangles.push(i);
+            }
+        }
+    }
+}
+
+impl<'a, C: ColorFormat> Tile<'a, C> {
+    // width and height are the size of the whole framebuffer.
+    pub fn rasterize<FS: FragmentShader>(
+        &mut self,
+        triangles: &[ScreenTriangle<FS::Varying>],
+        fs: &FS,
+        uniforms: &FS::Uniforms,
+        width: u32,
+        height: u32,
+    ) {
+        for i in 0..self.triangles.len() {
+            self.rasterize_triangle(&triangles[self.triangles[i]], fs, uniforms, width, height);
+        }
+    }
+
+    fn rasterize_triangle<FS: FragmentShader>(
+        &mut self,
+        triangle: &ScreenTriangle<FS::Varying>,
+        fs: &FS,
+        uniforms: &FS::Uniforms,
+        width: u32,
+        height: u32,
+    ) {
+        let [a, b, c] = triangle.fixed(width, height);
+        let samples = self.output.multisample.offsets();
+        let reach = self.output.multisample.reach();
+        let x_range = (self.x as i64, (self.x + self.width - 1) as i64);
+        let y_range = (self.y as i64, (self.y + self.height - 1) as i64);
+        let (min_x, max_x, min_y, max_y) = match bounds(a, b, c, reach, x_range, y_range) {
+            Some(bounds) => bounds,
+            None => return,
+        };
+
+        // Each edge is named after the vertex across from it, whose weight it gives
+        let edges = [Edge::new(b, c), Edge::new(c, a), Edge::new(a, b)];
+        let area = edges[0].at(0, 0) + edges[1].at(0, 0) + edges[2].at(0, 0);
+        if area == 0 {
+            return;
+        }
+        // Culling happened before clipping, what is wound the other way round gets drawn with
+        // two vertices swapped
+        if area < 0 {
+            return self.rasterize_triangle(&triangle.flipped(), fs, uniforms, width, height);
+        }
+
+        let (lane_x, lane_y) = (I32x8::new(LANE_X), I32x8::new(LANE_Y));
+        let (lane_xf, lane_yf) = (
+            F32x8::new(LANE_X.map(|x| x as f32)),
+            F32x8::new(LANE_Y.map(|y| y as f32)),
+        );
+        let step = |e: &Edge| (e.a << SUBPIXEL_BITS, e.b << SUBPIXEL_BITS);
+        // Edge values of the lanes relative to the first one. These fit in i32 for any
+        // framebuffer this rasterizer can sensibly handle.
+        let offsets = edges.map(|e| {
+            let (sx, sy) = step(&e);
+            I32x8::splat(sx as i32) * lane_x + I32x8::splat(sy as i32) * lane_y
+        });
+        let offsets_f = edges.map(|e| {
+            let (sx, sy) = step(&e);
+            F32x8::splat(sx as f32) * lane_xf + F32x8::splat(sy as f32) * lane_yf
+        });
+
+        let inv_area = F32x8::splat(1. / area as f32);
+        let depths = triangle.positions.map(|p| p.z);
+        let slope = triangle.depth_slope(width, height);
+        let bias = self.output.depth_bias.offset(depths, slope);
+        let [za, zb, zc] = depths.map(|z| F32x8::splat(z + bias));
+        let [inv_wa, inv_wb, inv_wc] = triangle.positions.map(|p| F32x8::splat(p.w));
+        let one = F32x8::splat(1.);
+
+        let (x_end, y_end) = (self.x + self.width, self.y + self.height);
+        let span = (BLOCK_SIZE - 1) as i64;
+
+        for by in (min_y & !(BLOCK_SIZE - 1)..=max_y).step_by(BLOCK_SIZE as usize) {
+            for bx in (min_x & !(BLOCK_SIZE - 1)..=max_x).step_by(BLOCK_SIZE as usize) {
+                // Check the block's corners, moved out by the reach of the samples: outside any
+                // edge means nothing to do, inside an edge means the samples need not be
+                // tested against it
+                let mut straddles = [false; 3];
+                let mut outside = false;
+                for (i, e) in edges.iter().enumerate() {
+                    let (sx, sy) = step(e);
+                    let v = e.at(bx, by) + e.bias;
+                    let (dx, dy) = (sx * span, sy * span);
+                    let slack = (e.a.abs() + e.b.abs()) * reach;
+                    if v + dx.max(0) + dy.max(0) + slack < 0 {
+                        outside = true;
+                    }
+                    straddles[i] = v + dx.min(0) + dy.min(0) - slack < 0;
+                }
+                if outside {
+                    continue;
+                }
+
+                for gy in (by..by + BLOCK_SIZE).step_by(2) {
+                    for gx in (bx..bx + BLOCK_SIZE).step_by(4) {
+                        let mut inside = 0xff;
+                        for (lane, (lx, ly)) in LANE_X.iter().zip(LANE_Y.iter()).enumerate() {
+                            if gx + *lx as u32 >= x_end || gy + *ly as u32 >= y_end {
+                                inside &= !(1 << lane);
+                            }
+                        }
+
+                        // One lane mask per sample
+                        let mut coverage = [0; MAX_SAMPLES];
+                        for (s, offset) in samples.iter().enumerate() {
+                            let mut signs = I32x8::splat(0);
+                            for i in 0..3 {
+                                if straddles[i] {
+                                    let e = &edges[i];
+                                    let v = e.at(gx, gy) + e.shift(*offset) + e.bias;
+                                    signs = signs | (I32x8::splat(v as i32) + offsets[i]);
+                                }
+                            }
+                            coverage[s] = inside & !signs.negative_mask();
+                        }
+                        if coverage.iter().all(|c| *c == 0) {
+                            continue;
+                        }
+
+                        // Linear weights at the pixel centers, or shifted to a sample
+                        let linear = |offset: (i64, i64)| {
+                            [0, 1, 2].map(|i| {
+                                let v = edges[i].at(gx, gy) + edges[i].shift(offset);
+                                (F32x8::splat(v as f32) + offsets_f[i]) * inv_area
+                            })
+                        };
+                        let [l1, l2, l3] = linear((0, 0));
+                        // z/w is affine in screen space, so depth keeps the linear weights
+                        let depths = (l1 * za + l2 * zb + l3 * zc).to_array();
+                        let mut sample_depths = [[0.; 8]; MAX_SAMPLES];
+                        for (s, offset) in samples.iter().enumerate() {
+                            if *offset == (0, 0) {
+                                sample_depths[s] = depths;
+                            } else if coverage[s] != 0 {
+                                let [s1, s2, s3] = linear(*offset);
+                                sample_depths[s] = (s1 * za + s2 * zb + s3 * zc).to_array();
+                            }
+                        }
+                        // Perspective-correct weights using the 1/w of each vertex
+                        let (p1, p2, p3) = (l1 * inv_wa, l2 * inv_wb, l3 * inv_wc);
+                        let inv_sum = one / (p1 + p2 + p3);
+                        let weights =
+                            [p1 * inv_sum, p2 * inv_sum, p3 * inv_sum].map(|p| p.to_array());
+
+                        for quad in 0..2 {
+                            let lanes = [2 * quad, 2 * quad + 1, 2 * quad + 4, 2 * quad + 5];
+                            let pixels = Quad {
+                                h: gx + 2 * quad as u32,
+                                w: gy,
+                                coverage: lanes.map(|l| {
+                                    (0..samples.len())
+                                        .filter(|s| coverage[*s] & (1 << l) != 0)
+                                        .fold(0, |mask, s| mask | 1 << s)
+                                }),
+                                depths: lanes.map(|l| sample_depths.map(|d| d[l])),
+                                center_depths: lanes.map(|l| depths[l]),
+                                weights: lanes
+                                    .map(|l| (weights[0][l], weights[1][l], weights[2][l])),
+                            };
+                            self.shade_quad(triangle, &pixels, fs, uniforms, height);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Pixels are shaded in 2x2 quads so that every fragment can look at its neighbours'
+    // varyings and get screen-space derivatives, like a GPU does. Lanes outside the triangle
+    // still get interpolated as helpers. A pixel is shaded once if any of its samples passes
+    // the depth test, and the color is blended into all that did.
+    fn shade_quad<FS: FragmentShader>(
+        &mut self,
+        triangle: &ScreenTriangle<FS::Varying>,
+        quad: &Quad,
+        fs: &FS,
+        uniforms: &FS::Uniforms,
+        height: u32,
+    ) {
+        if quad.coverage.iter().all(|c| *c == 0) {
+            return;
+        }
+
+        let [v1, v2, v3] = &triangle.varyings;
+        let varyings = quad
+            .weights
+            .map(|w| FS::Varying::interpolate(v1, v2, v3, w));
+
+        let OutputState {
+            multisample,
+            blend: mode,
+            depth_compare,
+            depth_write,
+            encoding,
+            ..
+        } = self.output;
+        let count = multisample.count();
+        for lane in 0..4 {
+            if quad.coverage[lane] == 0 {
+                continue;
+            }
+
+            let h = quad.h + (lane as u32 & 1);
+            let w = quad.w + (lane as u32 >> 1);
+            let row = (w - self.y) as usize;
+            let samples = (h - self.x) as usize * count..(h - self.x + 1) as usize * count;
+            let depths = &quad.depths[lane][..count];
+
+            let mut passed = 0;
+            let stored = &self.depth[row][samples.clone()];
+            for (s, (depth, stored)) in depths.iter().zip(stored).enumerate() {
+                if quad.coverage[lane] & (1 << s) != 0 && depth_compare.passes(*depth, *stored) {
+                    passed |= 1 << s;
+                }
+            }
+            if passed == 0 {
+                continue;
+            }
+
+            let quad_row = lane & 2;
+            let quad_column = lane & 1;
+            let fragment = Fragment {
+                x: h,
+                // NDC y points up while rows are stored top to bottom
+                y: height - 1 - w,
+                depth: quad.center_depths[lane],
+                varying: varyings[lane],
+                ddx: varyings[quad_row + 1].difference(&varyings[quad_row]),
+                ddy: varyings[quad_column].difference(&varyings[quad_column + 2]),
+            };
+
+            if let Some((rc, gc, bc, ac)) = fs.fragment(&fragment, uniforms) {
+                let color = Vec4::new(rc, gc, bc, ac);
+                let stored = self.color[row][samples.clone()]
+                    .iter_mut()
+                    .zip(&mut self.depth[row][samples]);
+                for (s, (stored_color, stored_depth)) in stored.enumerate() {
+                    if passed & (1 << s) == 0 {
+                        continue;
+                    }
+                    let color = match mode {
+                        // Nothing to read back
+                        BlendMode::Replace => color,
+                        _ => blend::blend(mode, color, stored_color.load(&encoding)),
+                    };
+                    *stored_color = C::store(color, &encoding, fragment.x, fragment.y);
+                    if depth_write {
+                        *stored_depth = depths[s];
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Tiles only write to their own pixels, so they can be drawn in any order or all at once.
+#[cfg(feature = "parallel")]
+pub fn for_each_tile<C, F>(tiles: &mut [Tile<C>], parallel: bool, f: F)
+where
+    C: ColorFormat,
+    F: Fn(&mut Tile<C>) + Send + Sync,
+{
+    use rayon::prelude::*;
+
+    if parallel {
+        tiles.par_iter_mut().for_each(f);
+    } else {
+        tiles.iter_mut().for_each(f);
+    }
+}
+
+#[cfg(not(feature = "parallel"))]
+pub fn for_each_tile<C, F>(tiles: &mut [Tile<C>], _: bool, f: F)
+where
+    C: ColorFormat,
+    F: Fn(&mut Tile<C>) + Send + Sync,
+{
+    tiles.iter_mut().for_each(f);
+}
+
+// The buffers a draw goes to and how.
+pub struct Target<'a, C> {
+    pub width: u32,
+    pub height: u32,
+    pub color: &'a mut [C],
+    pub depth: &'a mut [f32],
+    pub output: OutputState,
+    pub parallel: bool,
+}
+
+// Sorts the triangles into screen tiles and draws those.
+pub fn draw<C: ColorFormat, FS: FragmentShader>(
+    target: Target<C>,
+    triangles: &[ScreenTriangle<FS::Varying>],
+    fs: &FS,
+    uniforms: &FS::Uniforms,
+) {
+    let Target {
+        width,
+        height,
+        color,
+        depth,
+        output,
+        parallel,
+    } = target;
+    let mut tiles = split(width, height, output, color, depth);
+    bin(&mut tiles, triangles, width, height, output.multisample);
+    for_each_tile(&mut tiles, parallel, |tile| {
+        tile.rasterize(triangles, fs, uniforms, width, height)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::color::ColorSpace;
+
+    // Adds one to red for every sample written.
+    struct Count;
+
+    impl FragmentShader for Count {
+        type Uniforms = ();
+        type Varying = ();
+
+        fn fragment(&self, _: &Fragment<()>, _: &()) -> Option<(f32, f32, f32, f32)> {
+            Some((1., 0., 0., 1.))
+        }
+    }
+
+    const WIDTH: u32 = 150;
+    const HEIGHT: u32 = 100;
+
+    fn triangle(a: (f32, f32), b: (f32, f32), c: (f32, f32)) -> ScreenTriangle<()> {
+        let p = |(x, y): (f32, f32)| Vec4::new(x, y, 0.5, 1.);
+        ScreenTriangle {
+            positions: [p(a), p(b), p(c)],
+            varyings: [(); 3],
+        }
+    }
+
+    // How often every sample was written, rows stored top to bottom.
+    fn draw_counts(
+        triangles: &[ScreenTriangle<()>],
+        multisample: Multisample,
+        parallel: bool,
+    ) -> Vec<f32> {
+        let samples = (WIDTH * HEIGHT) as usize * multisample.count();
+        let mut color = vec![Vec4::zero(); samples];
+        let mut depth = vec![0.; samples];
+        let target = Target {
+            width: WIDTH,
+            height: HEIGHT,
+            color: &mut color,
+            depth: &mut depth,
+            output: OutputState {
+                multisample,
+                blend: BlendMode::Additive,
+                depth_compare: DepthCompare::Always,
+                depth_write: false,
+                depth_bias: DepthBias::default(),
+                encoding: Encoding::new(ColorSpace::Linear),
+            },
+            parallel,
+        };
+        draw(target, triangles, &Count, &());
+        color.iter().map(|c| c.x).collect()
+    }
+
+    // Tests every sample against the edges one at a time with plain integers, the reference
+    // for the lanes and block rejection of draw. Same layout as draw_counts.
+    fn reference_counts(triangles: &[ScreenTriangle<()>], multisample: Multisample) -> Vec<f32> {
+        let offsets = multisample.offsets();
+        let mut counts = vec![0.; (WIDTH * HEIGHT) as usize * offsets.len()];
+        for triangle in triangles {
+            let [a, b, c] = triangle.fixed(WIDTH, HEIGHT);
+            let mut edges = [Edge::new(b, c), Edge::new(c, a), Edge::new(a, b)];
+            if edges.iter().map(|e| e.at(0, 0)).sum::<i64>() < 0 {
+                edges = [Edge::new(c, b), Edge::new(a, c), Edge::new(b, a)];
+            }
+            for w in 0..HEIGHT {
+                for h in 0..WIDTH {
+                    for (s, offset) in offsets.iter().enumerate() {
+                        let inside = edges
+                            .iter()
+                            .all(|e| e.at(h, w) + e.shift(*offset) + e.bias >= 0);
+                        if inside {
+                            let row = (HEIGHT - 1 - w) as usize;
+                            counts[(row * WIDTH as usize + h as usize) * offsets.len() + s] += 1.;
+                        }
+                    }
+                }
+            }
+        }
+        counts
+    }
+
+    // Whether the sample lies strictly inside the convex polygon, None when it is on the
+    // outline. The corners are snapped like those of the triangles.
+    fn inside_polygon(polygon: &[(f32, f32)], h: u32, w: u32, offset: (i64, i64)) -> Option<bool> {
+        let snapped: Vec<(i64, i64)> = polygon
+            .iter()
+            .map(|p| triangle(*p, *p, *p).fixed(WIDTH, HEIGHT)[0])
+            .collect();
+        let signs: Vec<i64> = (0..snapped.len())
+            .map(|i| {
+                let e = Edge::new(snapped[i], snapped[(i + 1) % snapped.len()]);
+                (e.at(h, w) + e.shift(offset)).signum()
+            })
+            .collect();
+        if signs.contains(&0) {
+            None
+        } else {
+            Some(signs.iter().all(|s| *s == signs[0]))
+        }
+    }
+
+    fn check(polygon: &[(f32, f32)], triangles: &[ScreenTriangle<()>]) {
+        for multisample in [Multisample::Off, Multisample::X4, Multisample::X8] {
+            let counts = draw_counts(triangles, multisample, true);
+            assert!(counts == reference_counts(triangles, multisample));
+            assert!(counts == draw_counts(triangles, multisample, false));
+
+            let offsets = multisample.offsets();
+            for w in 0..HEIGHT {
+                for h in 0..WIDTH {
+                    for (s, offset) in offsets.iter().enumerate() {
+                        let row = (HEIGHT - 1 - w) as usize;
+                        let count = counts[(row * WIDTH as usize + h as usize) * offsets.len() + s];
+                        match inside_polygon(polygon, h, w, *offset) {
+                            Some(true) => assert_eq!(count, 1., "sample {} {} {}", h, w, s),
+                            Some(false) => assert_eq!(count, 0., "sample {} {} {}", h, w, s),
+                            None => assert!(count <= 1., "sample {} {} {}", h, w, s),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn shared_edge_is_watertight() {
+        let quad = [(-0.73, -0.61), (0.58, -0.67), (0.69, 0.77), (-0.52, 0.55)];
+        let triangles = [
+            triangle(quad[0], quad[1], quad[2]),
+            triangle(quad[0], quad[2], quad[3]),
+        ];
+        check(&quad, &triangles);
+    }
+
+    #[test]
+    fn fan_is_watertight() {
+        // Uneven spokes around an off-center hub, every other triangle wound the other way
+        let center = (0.03, -0.07);
+        let angles = [0.1f32, 0.9, 1.6, 2.2, 3.3, 4.1, 4.7, 5.6];
+        let rim: Vec<(f32, f32)> = angles
+            .iter()
+            .map(|a| (center.0 + 0.8 * a.cos(), center.1 + 0.85 * a.sin()))
+            .collect();
+        let triangles: Vec<ScreenTriangle<()>> = (0..rim.len())
+            .map(|i| {
+                let (p, q) = (rim[i], rim[(i + 1) % rim.len()]);
+                if i % 2 == 0 {
+                    triangle(center, p, q)
+                } else {
+                    triangle(center, q, p)
+                }
+            })
+            .collect();
+        check(&rim, &triangles);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::buffer::math::mat3::Mat3;
+use crate::buffer::math::mat4::Mat4;
+use crate::buffer::math::vec2::Vec2;
+use crate::buffer::math::vec3::Vec3;
+use crate::buffer::math::vec4::Vec4;
+use crate::buffer::mesh::Vertex;
+
+// Anything that can be carried from the vertex stage to the fragment stage. It has to be
+// blendable so the clipper can create new vertices and the rasterizer can interpolate it,
+// and shareable since tiles are rasterized on several threads.
+pub trait Varying: Copy + Send + Sync {
+    fn interpolate(a: &Self, b: &Self, c: &Self, weights: (f32, f32, f32)) -> Self;
+
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        Self::interpolate(self, other, other, (1. - t, t, 0.))
+    }
+
+    fn difference(&self, other: &Self) -> Self {
+        Self::interpolate(self, other, other, (1., -1., 0.))
+    }
+}
+
+impl Varying for () {
+    fn interpolate(_: &(), _: &(), _: &(), _: (f32, f32, f32)) {}
+}
+
+impl Varying for f32 {
+    fn interpolate(a: &f32, b: &f32, c: &f32, w: (f32, f32, f32)) -> f32 {
+        a * w.0 + b * w.1 + c * w.2
+    }
+}
+
+impl<const N: usize> Varying for [f32; N] {
+    fn interpolate(a: &Self, b: &Self, c: &Self, w: (f32, f32, f32)) -> Self {
+        let mut result = [0.; N];
+        for (i, r) in result.iter_mut().enumerate() {
+            *r = a[i] * w.0 + b[i] * w.1 + c[i] * w.2;
+        }
+        result
+    }
+}
+
+macro_rules! impl_varying {
+    ($($type:ty),+) => {
+        $(
+            impl Varying for $type {
+                fn interpolate(a: &Self, b: &Self, c: &Self, w: (f32, f32, f32)) -> Self {
+                    *a * w.0 + *b * w.1 + *c * w.2
+                }
+            }
+        )+
+    };
+}
+
+impl_varying!(Vec2, Vec3, Vec4);
+
+impl<A: Varying, B: Varying> Varying for (A, B) {
+    fn interpolate(a: &Self, b: &Self, c: &Self, w: (f32, f32, f32)) -> Self {
+        (
+            A::interpolate(&a.0, &b.0, &c.0, w),
+            B::interpolate(&a.1, &b.1, &c.1, w),
+        )
+    }
+}
+
+impl<A: Varying, B: Varying, C: Varying> Varying for (A, B, C) {
+    fn interpolate(a: &Self, b: &Self, c: &Self, w: (f32, f32, f32)) -> Self {
+        (
+            A::interpolate(&a.0, &b.0, &c.0, w),
+            B::interpolate(&a.1, &b.1, &c.1, w),
+            C::interpolate(&a.2, &b.2, &c.2, w),
+        )
+    }
+}
+
+// Snapshot of the matrices held by the buffer at draw time. normal takes object space
+// normals to view space.
+#[derive(Clone, Copy)]
+pub struct Transforms {
+    pub proj: Mat4,
+    pub world: Mat4,
+    pub obj: Mat4,
+    pub obj2world: Mat4,
+    pub obj2proj: Mat4,
+    pub normal: Mat3,
+}
+
+// ddx and ddy hold the change of the varyings to the next pixel on the right and below,
+// taken from the 2x2 quad the fragment was shaded in.
+pub struct Fragment<V> {
+    pub x: u32,
+    pub y: u32,
+    pub depth: f32,
+    pub varying: V,
+    pub ddx: V,
+    pub ddy: V,
+}
+
+pub trait VertexShader {
+    type Uniforms;
+    type Varying: Varying;
+
+    // Returns the clip-space position and the varyings of the vertex.
+    fn vertex(
+        &self,
+        vertex: &Vertex,
+        transforms: &Transforms,
+        uniforms: &Self::Uniforms,
+    ) -> (Vec4, Self::Varying);
+}
+
+// Runs on several threads at once, see raster.rs.
+pub trait FragmentShader: Sync {
+    type Uniforms: Sync;
+    type Varying: Varying;
+
+    // Returns the normalized RGBA color of the fragment, or None to discard it.
+    fn fragment(
+        &self,
+        fragment: &Fragment<Self::Varying>,
+        uniforms: &Self::Uniforms,
+    ) -> Option<(f32, f32, f32, f32)>;
+}
+
+// Outputs the interpolated vertex color, used by Buffer::draw_triangle.
+pub struct VertexColor;
+
+impl FragmentShader for VertexColor {
+    type Uniforms = ();
+    type Varying = [f32; 4];
+
+    fn fragment(&self, fragment: &Fragment<[f32; 4]>, _: &()) -> Option<(f32, f32, f32, f32)> {
+        let c = fragment.varying;
+        Some((c[0], c[1], c[2], c[3]))
+    }
+}
@@ -0,0 +1,174 @@
+use std::ops::Add;
+use std::ops::BitOr;
+use std::ops::Div;
+use std::ops::Mul;
+
+// Eight lanes of i32 or f32, just the operations the rasterizer needs. With the simd feature
+// they map onto wide's portable vectors, otherwise onto plain arrays. Both give the same
+// results bit for bit, there is no fused or approximate arithmetic in either.
+#[cfg(feature = "simd")]
+#[derive(Clone, Copy, Debug)]
+pub struct I32x8(wide::i32x8);
+
+#[cfg(feature = "simd")]
+#[derive(Clone, Copy, Debug)]
+pub struct F32x8(wide::f32x8);
+
+#[cfg(feature = "simd")]
+impl I32x8 {
+    pub fn new(lanes: [i32; 8]) -> I32x8 {
+        I32x8(wide::i32x8::new(lanes))
+    }
+
+    pub fn splat(value: i32) -> I32x8 {
+        I32x8(wide::i32x8::splat(value))
+    }
+
+    // Bit i is set when lane i is negative.
+    pub fn negative_mask(self) -> u32 {
+        self.0.to_bitmask()
+    }
+}
+
+#[cfg(feature = "simd")]
+impl F32x8 {
+    pub fn new(lanes: [f32; 8]) -> F32x8 {
+        F32x8(wide::f32x8::new(lanes))
+    }
+
+    pub fn splat(value: f32) -> F32x8 {
+        F32x8(wide::f32x8::splat(value))
+    }
+
+    pub fn to_array(self) -> [f32; 8] {
+        self.0.to_array()
+    }
+}
+
+#[cfg(feature = "simd")]
+macro_rules! impl_lane_op {
+    ($type:ident, $trait:ident, $method:ident) => {
+        impl $trait for $type {
+            type Output = $type;
+
+            fn $method(self, rhs: $type) -> $type {
+                $type(self.0.$method(rhs.0))
+            }
+        }
+    };
+}
+
+#[cfg(not(feature = "simd"))]
+#[derive(Clone, Copy, Debug)]
+pub struct I32x8([i32; 8]);
+
+#[cfg(not(feature = "simd"))]
+#[derive(Clone, Copy, Debug)]
+pub struct F32x8([f32; 8]);
+
+#[cfg(not(feature = "simd"))]
+impl I32x8 {
+    pub fn new(lanes: [i32; 8]) -> I32x8 {
+        I32x8(lanes)
+    }
+
+    pub fn splat(value: i32) -> I32x8 {
+        I32x8([value; 8])
+    }
+
+    // Bit i is set when lane i is negative.
+    pub fn negative_mask(self) -> u32 {
+        let mut mask = 0;
+        for (i, lane) in self.0.iter().enumerate() {
+            if *lane < 0 {
+                mask |= 1 << i;
+            }
+        }
+        mask
+    }
+}
+
+#[cfg(not(feature = "simd"))]
+impl F32x8 {
+    pub fn new(lanes: [f32; 8]) -> F32x8 {
+        F32x8(lanes)
+    }
+
+    pub fn splat(value: f32) -> F32x8 {
+        F32x8([value; 8])
+    }
+
+    pub fn to_array(self) -> [f32; 8] {
+        self.0
+    }
+}
+
+#[cfg(not(feature = "simd"))]
+macro_rules! impl_lane_op {
+    ($type:ident, $trait:ident, $method:ident) => {
+        impl $trait for $type {
+            type Output = $type;
+
+            fn $method(mut self, rhs: $type) -> $type {
+                for (a, b) in self.0.iter_mut().zip(rhs.0.iter()) {
+                    *a = a.$method(*b);
+                }
+                self
+            }
+        }
+    };
+}
+
+impl_lane_op!(I32x8, Add, add);
+impl_lane_op!(I32x8, Mul, mul);
+impl_lane_op!(I32x8, BitOr, bitor);
+impl_lane_op!(F32x8, Add, add);
+impl_lane_op!(F32x8, Mul, mul);
+impl_lane_op!(F32x8, Div, div);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Edge values like the rasterizer's, positive and negative around zero.
+    fn ints(seed: i32) -> [i32; 8] {
+        [0, 1, 2, 3, 4, 5, 6, 7].map(|i| (i * 7919 + seed * 104729) % 65536 - 32768)
+    }
+
+    fn floats(seed: i32) -> [f32; 8] {
+        ints(seed).map(|i| i as f32 / 97.)
+    }
+
+    // Whichever implementation is compiled has to match lane by lane scalar arithmetic.
+    #[test]
+    fn lanes_match_scalar() {
+        for seed in 0..64 {
+            let (a, b) = (ints(seed), ints(seed + 1000));
+            let (x, y) = (I32x8::new(a), I32x8::new(b));
+
+            let sum = x + y;
+            let scaled = x * I32x8::splat(3);
+            let ored = x | y;
+            let mut expected = [0; 3];
+            for i in 0..8 {
+                let lane = |v: i32| (v < 0) as u32;
+                expected[0] |= lane(a[i] + b[i]) << i;
+                expected[1] |= lane(a[i] * 3) << i;
+                expected[2] |= lane(a[i] | b[i]) << i;
+            }
+            assert_eq!(sum.negative_mask(), expected[0]);
+            assert_eq!(scaled.negative_mask(), expected[1]);
+            assert_eq!(ored.negative_mask(), expected[2]);
+
+            let (fa, fb) = (floats(seed), floats(seed + 1000));
+            let (fx, fy) = (F32x8::new(fa), F32x8::new(fb));
+            let result = ((fx + fy) * fx / F32x8::splat(1.5)).to_array();
+            for i in 0..8 {
+                let lane = (fa[i] + fb[i]) * fa[i] / 1.5;
+                assert_eq!(result[i].to_bits(), lane.to_bits());
+            }
+        }
+        assert_eq!(I32x8::splat(-1).negative_mask(), 0xff);
+        assert_eq!(I32x8::splat(0).negative_mask(), 0);
+    }
+}
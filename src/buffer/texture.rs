@@ -0,0 +1,521 @@
+#[cfg(feature = "image-io")]
+use image::GenericImageView;
+
+use crate::buffer::clamp;
+#[cfg(feature = "image-io")]
+use crate::buffer::color::ColorSpace;
+use crate::buffer::math::vec2::Vec2;
+use crate::buffer::math::vec4::Vec4;
+use crate::buffer::mesh::Vertex;
+use crate::buffer::shader::Fragment;
+use crate::buffer::shader::FragmentShader;
+use crate::buffer::shader::Transforms;
+use crate::buffer::shader::VertexShader;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MipFilter {
+    None,
+    Nearest,
+    Linear,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Wrap {
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+// Texels are stored row by row as linear RGB, with v = 0 at the first row.
+// mips holds the levels below this one, each half the size of the previous.
+pub struct Texture {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<[f32; 3]>,
+    pub mips: Vec<Texture>,
+}
+
+impl Texture {
+    pub fn new(width: u32, height: u32, data: Vec<[f32; 3]>) -> Texture {
+        assert_eq!(data.len(), (width * height) as usize);
+        Texture {
+            width,
+            height,
+            data,
+            mips: Vec::new(),
+        }
+    }
+
+    // space is what the file holds, Srgb for colors and Linear for data such as normal maps.
+    #[cfg(feature = "image-io")]
+    pub fn load(path: &str, space: ColorSpace) -> Result<Texture, image::ImageError> {
+        let img = image::open(path)?;
+        let (width, height) = img.dimensions();
+
+        let mut data = Vec::with_capacity((width * height) as usize);
+        let decode = |c: u8| space.to_linear(c as f32 / 255.0);
+        for (_, _, pixel) in img.pixels() {
+            data.push([decode(pixel[0]), decode(pixel[1]), decode(pixel[2])]);
+        }
+
+        let mut texture = Texture::new(width, height, data);
+        texture.generate_mipmaps();
+        Ok(texture)
+    }
+
+    // Builds the full chain down to 1x1 with a box filter.
+    pub fn generate_mipmaps(&mut self) {
+        self.mips.clear();
+
+        loop {
+            let last = self.level(self.mips.len());
+            if last.width == 1 && last.height == 1 {
+                break;
+            }
+            let next = last.downsample();
+            self.mips.push(next);
+        }
+    }
+
+    // Odd sizes round up, the last row or column then averages with itself through the clamp.
+    fn downsample(&self) -> Texture {
+        let width = self.width.div_ceil(2);
+        let height = self.height.div_ceil(2);
+
+        let mut data = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let (x, y) = (2 * x as i32, 2 * y as i32);
+                let mut sum = [0.; 3];
+                for texel in [
+                    self.texel(x, y, Wrap::Clamp),
+                    self.texel(x + 1, y, Wrap::Clamp),
+                    self.texel(x, y + 1, Wrap::Clamp),
+                    self.texel(x + 1, y + 1, Wrap::Clamp),
+                ]
+                .iter()
+                {
+                    sum[0] += texel[0] * 0.25;
+                    sum[1] += texel[1] * 0.25;
+                    sum[2] += texel[2] * 0.25;
+                }
+                data.push(sum);
+            }
+        }
+
+        Texture::new(width, height, data)
+    }
+
+    pub fn level_count(&self) -> usize {
+        self.mips.len() + 1
+    }
+
+    pub fn level(&self, level: usize) -> &Texture {
+        if level == 0 {
+            self
+        } else {
+            &self.mips[usize::min(level, self.mips.len()) - 1]
+        }
+    }
+
+    pub fn texel(&self, x: i32, y: i32, wrap: Wrap) -> [f32; 3] {
+        let x = wrap_coord(x, self.width as i32, wrap);
+        let y = wrap_coord(y, self.height as i32, wrap);
+        self.data[(x + y * self.width as i32) as usize]
+    }
+}
+
+fn wrap_coord(c: i32, size: i32, wrap: Wrap) -> i32 {
+    match wrap {
+        Wrap::Repeat => c.rem_euclid(size),
+        Wrap::Clamp => c.max(0).min(size - 1),
+        Wrap::Mirror => {
+            let m = c.rem_euclid(2 * size);
+            if m >= size {
+                2 * size - 1 - m
+            } else {
+                m
+            }
+        }
+    }
+}
+
+fn lerp(a: [f32; 3], b: [f32; 3], t: f32) -> [f32; 3] {
+    [
+        a[0] + (b[0] - a[0]) * t,
+        a[1] + (b[1] - a[1]) * t,
+        a[2] + (b[2] - a[2]) * t,
+    ]
+}
+
+// max_anisotropy above 1 takes up to that many probes along the longer axis of the
+// pixel footprint when sampling with derivatives.
+#[derive(Clone, Copy, Debug)]
+pub struct Sampler {
+    pub filter: Filter,
+    pub mip_filter: MipFilter,
+    pub wrap: Wrap,
+    pub max_anisotropy: u32,
+}
+
+impl Sampler {
+    pub fn new(filter: Filter, wrap: Wrap) -> Sampler {
+        Sampler {
+            filter,
+            mip_filter: MipFilter::None,
+            wrap,
+            max_anisotropy: 1,
+        }
+    }
+
+    pub fn trilinear(wrap: Wrap) -> Sampler {
+        Sampler {
+            filter: Filter::Bilinear,
+            mip_filter: MipFilter::Linear,
+            wrap,
+            max_anisotropy: 1,
+        }
+    }
+
+    // Samples using the screen-space derivatives of uv to pick the mip level.
+    pub fn sample_grad(&self, texture: &Texture, uv: Vec2, ddx: Vec2, ddy: Vec2) -> [f32; 3] {
+        if self.mip_filter == MipFilter::None {
+            return self.sample(texture, uv);
+        }
+
+        let (w, h) = (texture.width as f32, texture.height as f32);
+        let size = Vec2::new(w, h);
+        let len_x = (ddx * size).length();
+        let len_y = (ddy * size).length();
+
+        let (major, major_len, minor_len) = if len_x >= len_y {
+            (ddx, len_x, len_y)
+        } else {
+            (ddy, len_y, len_x)
+        };
+
+        let probes = if self.max_anisotropy > 1 && minor_len > 0. {
+            u32::min((major_len / minor_len).ceil() as u32, self.max_anisotropy).max(1)
+        } else {
+            1
+        };
+
+        let lod = f32::max(major_len / probes as f32, 1e-8).log2();
+        if probes == 1 {
+            return self.sample_level(texture, uv, lod);
+        }
+
+        // Spread the probes evenly over the major axis of the footprint and average them
+        let mut sum = [0.; 3];
+        for i in 0..probes {
+            let t = (i as f32 + 0.5) / probes as f32 - 0.5;
+            let c = self.sample_level(texture, uv + major * t, lod);
+            sum[0] += c[0] / probes as f32;
+            sum[1] += c[1] / probes as f32;
+            sum[2] += c[2] / probes as f32;
+        }
+        sum
+    }
+
+    pub fn sample_level(&self, texture: &Texture, uv: Vec2, lod: f32) -> [f32; 3] {
+        let max_level = (texture.level_count() - 1) as f32;
+        let lod = clamp(lod, 0., max_level);
+
+        match self.mip_filter {
+            MipFilter::None => self.sample(texture, uv),
+            MipFilter::Nearest => self.sample(texture.level(lod.round() as usize), uv),
+            MipFilter::Linear => {
+                let lower = lod.floor();
+                let a = self.sample(texture.level(lower as usize), uv);
+                if lower == lod {
+                    return a;
+                }
+                let b = self.sample(texture.level(lower as usize + 1), uv);
+                lerp(a, b, lod - lower)
+            }
+        }
+    }
+
+    // Samples the given level only, ignoring the mip chain.
+    pub fn sample(&self, texture: &Texture, uv: Vec2) -> [f32; 3] {
+        let u = uv.x * texture.width as f32;
+        let v = uv.y * texture.height as f32;
+
+        match self.filter {
+            Filter::Nearest => texture.texel(u.floor() as i32, v.floor() as i32, self.wrap),
+            Filter::Bilinear => {
+                let u = u - 0.5;
+                let v = v - 0.5;
+                let x = u.floor();
+                let y = v.floor();
+                let tx = u - x;
+                let ty = v - y;
+                // Far out of range uv saturate, the step to the next texel must not overflow
+                let (x0, y0) = (x as i32, y as i32);
+                let (x1, y1) = (x0.saturating_add(1), y0.saturating_add(1));
+
+                let top = lerp(
+                    texture.texel(x0, y0, self.wrap),
+                    texture.texel(x1, y0, self.wrap),
+                    tx,
+                );
+                let bottom = lerp(
+                    texture.texel(x0, y1, self.wrap),
+                    texture.texel(x1, y1, self.wrap),
+                    tx,
+                );
+                lerp(top, bottom, ty)
+            }
+        }
+    }
+}
+
+// Unlit, outputs the texture color at the vertex uvs. Draw with Buffer::draw_mesh, the
+// derivatives of the uvs pick the mip level.
+pub struct TextureShader<'a> {
+    pub texture: &'a Texture,
+    pub sampler: Sampler,
+}
+
+impl VertexShader for TextureShader<'_> {
+    type Uniforms = ();
+    type Varying = Vec2;
+
+    fn vertex(&self, vertex: &Vertex, transforms: &Transforms, _: &()) -> (Vec4, Vec2) {
+        (transforms.obj2proj * vertex.position.extend(1.), vertex.uv)
+    }
+}
+
+impl FragmentShader for TextureShader<'_> {
+    type Uniforms = ();
+    type Varying = Vec2;
+
+    fn fragment(&self, fragment: &Fragment<Vec2>, _: &()) -> Option<(f32, f32, f32, f32)> {
+        let f = fragment;
+        let c = self
+            .sampler
+            .sample_grad(self.texture, f.varying, f.ddx, f.ddy);
+        Some((c[0], c[1], c[2], 1.))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::buffer::cull::CullMode;
+    use crate::buffer::math::mat4::Mat4;
+    use crate::buffer::math::matrix::Matrix;
+    use crate::buffer::math::vec3::Vec3;
+    use crate::buffer::mesh::Mesh;
+    use crate::buffer::pixel;
+    use crate::buffer::Buffer;
+
+    fn assert_close(a: [f32; 3], b: [f32; 3]) {
+        let close = a.iter().zip(b.iter()).all(|(x, y)| (x - y).abs() < 1e-5);
+        assert!(close, "{:?} != {:?}", a, b);
+    }
+
+    // A 4x1 ramp with the texel index in red.
+    fn ramp() -> Texture {
+        Texture::new(4, 1, (0..4).map(|i| [i as f32, 0., 0.]).collect())
+    }
+
+    #[test]
+    fn wrap_modes() {
+        let texture = ramp();
+        let red = |x, wrap| texture.texel(x, 0, wrap)[0];
+
+        let repeat: Vec<f32> = (-5..9).map(|x| red(x, Wrap::Repeat)).collect();
+        let clamp: Vec<f32> = (-5..9).map(|x| red(x, Wrap::Clamp)).collect();
+        let mirror: Vec<f32> = (-5..9).map(|x| red(x, Wrap::Mirror)).collect();
+
+        assert_eq!(
+            repeat,
+            [3., 0., 1., 2., 3., 0., 1., 2., 3., 0., 1., 2., 3., 0.]
+        );
+        assert_eq!(
+            clamp,
+            [0., 0., 0., 0., 0., 0., 1., 2., 3., 3., 3., 3., 3., 3.]
+        );
+        assert_eq!(
+            mirror,
+            [3., 3., 2., 1., 0., 0., 1., 2., 3., 3., 2., 1., 0., 0.]
+        );
+    }
+
+    #[test]
+    fn bilinear_weights() {
+        let texture = ramp();
+        let sampler = Sampler::new(Filter::Bilinear, Wrap::Clamp);
+        let red = |u| sampler.sample(&texture, Vec2::new(u, 0.5))[0];
+
+        // Texel centers give the texel itself, in between is a linear blend
+        for i in 0..4 {
+            assert!((red((i as f32 + 0.5) / 4.) - i as f32).abs() < 1e-5);
+        }
+        assert!((red(0.5) - 1.5).abs() < 1e-5);
+        assert!((red(0.3125) - 0.75).abs() < 1e-5);
+
+        // Outside the centers of the edge texels the wrap mode decides the neighbour
+        assert!((red(0.) - 0.).abs() < 1e-5);
+        let repeat = Sampler::new(Filter::Bilinear, Wrap::Repeat);
+        assert!((repeat.sample(&texture, Vec2::new(0., 0.5))[0] - 1.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn nearest_picks_the_covering_texel() {
+        let texture = ramp();
+        let sampler = Sampler::new(Filter::Nearest, Wrap::Repeat);
+        assert_close(sampler.sample(&texture, Vec2::new(0.3, 0.5)), [1., 0., 0.]);
+        assert_close(sampler.sample(&texture, Vec2::new(1.9, 0.5)), [3., 0., 0.]);
+        assert_close(sampler.sample(&texture, Vec2::new(-0.1, 0.5)), [3., 0., 0.]);
+    }
+
+    #[test]
+    fn far_out_of_range_uvs_do_not_overflow() {
+        let texture = ramp();
+        for wrap in [Wrap::Repeat, Wrap::Clamp, Wrap::Mirror] {
+            let sampler = Sampler::new(Filter::Bilinear, wrap);
+            for uv in [Vec2::splat(1e12), Vec2::splat(-1e12), Vec2::splat(f32::MAX)] {
+                sampler.sample(&texture, uv);
+            }
+        }
+    }
+
+    // Every level filled with its own index.
+    fn levels(width: u32, height: u32) -> Texture {
+        let mut texture = Texture::new(width, height, vec![[0.; 3]; (width * height) as usize]);
+        texture.generate_mipmaps();
+        for (i, mip) in texture.mips.iter_mut().enumerate() {
+            for texel in mip.data.iter_mut() {
+                *texel = [i as f32 + 1.; 3];
+            }
+        }
+        texture
+    }
+
+    #[test]
+    fn mip_chain_sizes() {
+        let sizes = |texture: &Texture| -> Vec<(u32, u32)> {
+            (0..texture.level_count())
+                .map(|i| (texture.level(i).width, texture.level(i).height))
+                .collect()
+        };
+        assert_eq!(sizes(&levels(8, 2)), [(8, 2), (4, 1), (2, 1), (1, 1)]);
+        assert_eq!(sizes(&levels(5, 3)), [(5, 3), (3, 2), (2, 1), (1, 1)]);
+        assert_eq!(sizes(&levels(1, 1)), [(1, 1)]);
+    }
+
+    #[test]
+    fn odd_sizes_keep_the_last_row_and_column() {
+        let data = (0..9).map(|i| [i as f32, 0., 0.]).collect();
+        let mut texture = Texture::new(3, 3, data);
+        texture.generate_mipmaps();
+
+        let mip = texture.level(1);
+        let red: Vec<f32> = mip.data.iter().map(|texel| texel[0]).collect();
+        assert_eq!(red, [2., 3.5, 6.5, 8.]);
+        assert_close(texture.level(2).data[0], [5., 0., 0.]);
+    }
+
+    #[test]
+    fn lod_follows_the_derivatives() {
+        let texture = levels(16, 16);
+        let mut sampler = Sampler::trilinear(Wrap::Repeat);
+        sampler.mip_filter = MipFilter::Nearest;
+        let lod = |texels: f32| {
+            let d = texels / 16.;
+            sampler.sample_grad(
+                &texture,
+                Vec2::splat(0.5),
+                Vec2::new(d, 0.),
+                Vec2::new(0., d),
+            )[0]
+        };
+
+        assert_eq!(lod(0.5), 0.);
+        assert_eq!(lod(1.), 0.);
+        assert_eq!(lod(2.), 1.);
+        assert_eq!(lod(4.), 2.);
+        assert_eq!(lod(16.), 4.);
+        assert_eq!(lod(1000.), 4.);
+
+        sampler.mip_filter = MipFilter::Linear;
+        let d = f32::powf(2., 1.25) / 16.;
+        let c = sampler.sample_grad(
+            &texture,
+            Vec2::splat(0.5),
+            Vec2::new(d, 0.),
+            Vec2::new(0., d),
+        );
+        assert!((c[0] - 1.25).abs() < 1e-4);
+    }
+
+    #[test]
+    fn anisotropic_probes() {
+        let texture = {
+            let mut texture = Texture::new(8, 1, (0..8).map(|i| [i as f32, 0., 0.]).collect());
+            texture.generate_mipmaps();
+            texture
+        };
+        let mut sampler = Sampler::new(Filter::Nearest, Wrap::Repeat);
+        sampler.mip_filter = MipFilter::Nearest;
+
+        // Four texels across, one down, in the middle of texel 4
+        let uv = Vec2::new(4.5 / 8., 0.5);
+        let (ddx, ddy) = (Vec2::new(0.5, 0.), Vec2::new(0., 1.));
+
+        // One probe takes level 2, whose second texel averages 4 to 7
+        assert_close(sampler.sample_grad(&texture, uv, ddx, ddy), [5.5, 0., 0.]);
+
+        // Four probes stay on level 0 and hit texels 3 to 6
+        sampler.max_anisotropy = 4;
+        assert_close(sampler.sample_grad(&texture, uv, ddx, ddy), [4.5, 0., 0.]);
+
+        // Two probes go a level up, to the texels averaging 2 and 3, and 4 and 5
+        sampler.max_anisotropy = 2;
+        assert_close(sampler.sample_grad(&texture, uv, ddx, ddy), [3.5, 0., 0.]);
+    }
+
+    #[test]
+    fn texture_shader_maps_uvs() {
+        let (red, green, blue, white) = ([1., 0., 0.], [0., 1., 0.], [0., 0., 1.], [1., 1., 1.]);
+        let texture = Texture::new(2, 2, vec![red, green, blue, white]);
+
+        // A quad filling the screen, uv (0, 0) in the top left corner
+        let mut quad = Mesh::construct();
+        for (x, y) in [(-1., 1.), (1., 1.), (1., -1.), (-1., -1.)] {
+            quad.vertices.push(Vertex {
+                position: Vec3::new(x, y, 0.5),
+                normal: Vec3::new(0., 0., -1.),
+                uv: Vec2::new((x + 1.) / 2., (1. - y) / 2.),
+            });
+        }
+        quad.indices = vec![[0, 1, 2], [0, 2, 3]];
+        quad.v_size = 4;
+        quad.t_size = 2;
+
+        let mut buf = Buffer::new(8, 8, Mat4::identity(), Mat4::identity());
+        buf.cull_mode = CullMode::None;
+        let shader = TextureShader {
+            texture: &texture,
+            sampler: Sampler::new(Filter::Nearest, Wrap::Clamp),
+        };
+        buf.draw_mesh(&quad, &shader, &shader, &());
+
+        let at = |x: usize, y: usize| {
+            let (r, g, b, _) = pixel::unpack(buf.pixels()[x + y * 8]);
+            [r as f32 / 255., g as f32 / 255., b as f32 / 255.]
+        };
+        assert_close(at(1, 1), red);
+        assert_close(at(6, 1), green);
+        assert_close(at(1, 6), blue);
+        assert_close(at(6, 6), white);
+    }
+}
@@ -0,0 +1,103 @@
+use crate::buffer::clamp;
+use crate::buffer::math::vec3::Vec3;
+use crate::buffer::math::vec4::Vec4;
+
+// Curves squeezing linear HDR colors into [0, 1].
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ToneMap {
+    // Cuts off at 1, what an 8-bit target does by itself
+    Clamp,
+    // x / (1 + x)
+    Reinhard,
+    // Krzysztof Narkowicz's fit of the ACES filmic curve
+    Aces,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ToneMapping {
+    pub operator: ToneMap,
+    // In stops, every one doubles the colors before they go through the curve
+    pub exposure: f32,
+}
+
+impl ToneMapping {
+    pub fn new(operator: ToneMap) -> ToneMapping {
+        ToneMapping {
+            operator,
+            exposure: 0.,
+        }
+    }
+
+    // Alpha is only clamped.
+    pub fn apply(&self, color: Vec4) -> Vec4 {
+        let c = (color.xyz() * self.exposure.exp2()).max(Vec3::zero());
+        let mapped = match self.operator {
+            ToneMap::Clamp => c,
+            ToneMap::Reinhard => c / (c + Vec3::splat(1.)),
+            ToneMap::Aces => {
+                let numerator = c * (c * 2.51 + Vec3::splat(0.03));
+                let denominator = c * (c * 2.43 + Vec3::splat(0.59)) + Vec3::splat(0.14);
+                numerator / denominator
+            }
+        };
+        mapped.min(Vec3::splat(1.)).extend(clamp(color.w, 0., 1.))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(operator: ToneMap, exposure: f32, value: f32) -> f32 {
+        let mapping = ToneMapping { operator, exposure };
+        mapping.apply(Vec4::new(value, value, value, 1.)).x
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn known_values() {
+        assert_close(map(ToneMap::Clamp, 0., 0.25), 0.25);
+        assert_close(map(ToneMap::Clamp, 0., 4.), 1.);
+
+        assert_close(map(ToneMap::Reinhard, 0., 0.), 0.);
+        assert_close(map(ToneMap::Reinhard, 0., 1.), 0.5);
+        assert_close(map(ToneMap::Reinhard, 0., 3.), 0.75);
+
+        assert_close(map(ToneMap::Aces, 0., 0.), 0.);
+        assert_close(map(ToneMap::Aces, 0., 1.), 2.54 / 3.16);
+        assert_close(map(ToneMap::Aces, 0., 100.), 1.);
+    }
+
+    #[test]
+    fn curves_rise_monotonically() {
+        for operator in [ToneMap::Reinhard, ToneMap::Aces] {
+            let values: Vec<f32> = (0..=400)
+                .map(|i| map(operator, 0., i as f32 * 0.05))
+                .collect();
+            assert!(values.windows(2).all(|w| w[0] <= w[1]), "{:?}", operator);
+            assert!(values.iter().all(|v| (0. ..=1.).contains(v)));
+        }
+    }
+
+    #[test]
+    fn exposure() {
+        // Every stop doubles the input
+        assert_close(map(ToneMap::Clamp, 1., 0.25), 0.5);
+        assert_close(map(ToneMap::Clamp, -2., 2.), 0.5);
+        assert_close(map(ToneMap::Reinhard, -1., 2.), 0.5);
+        assert_close(map(ToneMap::Aces, 2., 0.25), map(ToneMap::Aces, 0., 1.));
+    }
+
+    #[test]
+    fn negative_colors_and_alpha() {
+        let mapping = ToneMapping::new(ToneMap::Reinhard);
+        let mapped = mapping.apply(Vec4::new(-1., 1., 3., 2.));
+        assert_eq!(mapped.x, 0.);
+        assert_close(mapped.y, 0.5);
+        assert_close(mapped.z, 0.75);
+        assert_eq!(mapped.w, 1.);
+    }
+}
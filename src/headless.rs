@@ -0,0 +1,566 @@
+use std::path::Path;
+
+use ruster::buffer::blend;
+use ruster::buffer::blend::BlendMode;
+use ruster::buffer::camera::Camera;
+use ruster::buffer::color::Color;
+use ruster::buffer::color::ColorSpace;
+use ruster::buffer::cull::CullMode;
+use ruster::buffer::depth::DepthRange;
+use ruster::buffer::gltf::load_gltf;
+use ruster::buffer::light::Light;
+use ruster::buffer::light::Lighting;
+use ruster::buffer::light::Material;
+use ruster::buffer::light::ShadingMode;
+use ruster::buffer::math::mat4::Mat4;
+use ruster::buffer::math::matrix::Matrix;
+use ruster::buffer::math::vec3::Vec3;
+use ruster::buffer::mesh::*;
+use ruster::buffer::obj::load_obj;
+use ruster::buffer::pixel::Encoding;
+use ruster::buffer::raster::Multisample;
+use ruster::buffer::tonemap::ToneMap;
+use ruster::buffer::tonemap::ToneMapping;
+use ruster::buffer::Buffer;
+use ruster::buffer::Savable;
+
+pub const USAGE: &str =
+    "usage: ruster render <model.obj|model.gltf|model.glb|cube|sphere|cone|torus> [options]
+
+options:
+  -o, --output PATH    output file, frame numbers are appended when rendering several (out.png).
+                       .exr and .hdr files get the linear HDR colors
+  -s, --size WxH       resolution in pixels (800x800)
+  --eye X,Y,Z          camera position (0,0,5)
+  --target X,Y,Z       point the camera looks at (0,0,0)
+  --up X,Y,Z           camera up vector (0,1,0)
+  --fov DEGREES        vertical field of view (45)
+  --near DISTANCE      near clipping plane (0.1)
+  --far DISTANCE       far clipping plane (1000)
+  --depth MODE         standard or reversed, which keeps more precision far away (standard)
+  --frames N           orbit the camera around the target over N frames (1)
+  --shading MODE       gouraud or phong (phong)
+  --msaa N             samples per pixel, 1, 2, 4 or 8 (1)
+  --channels MODE      rgb, or rgba for a transparent background (rgb)
+  --tonemap MODE       render in HDR and map to the output with clamp, reinhard or aces
+  --exposure STOPS     brightens or darkens before tone mapping (0)
+  --color-space MODE   what the 8-bit output is encoded as, srgb or linear (srgb)
+  --dither MODE        on to dither colors when quantizing them to 8 bits (off)";
+
+pub struct Options {
+    pub model: String,
+    pub output: String,
+    pub width: u32,
+    pub height: u32,
+    pub eye: Vec3,
+    pub target: Vec3,
+    pub up: Vec3,
+    pub fov: f32,
+    pub near: f32,
+    pub far: f32,
+    pub depth_range: DepthRange,
+    pub frames: u32,
+    pub shading: ShadingMode,
+    pub msaa: Multisample,
+    pub alpha: bool,
+    pub tone_map: Option<ToneMap>,
+    pub exposure: f32,
+    pub color_space: ColorSpace,
+    pub dither: bool,
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str, name: &str) -> Result<T, String> {
+    value
+        .parse::<T>()
+        .map_err(|_| format!("invalid value '{}' for {}", value, name))
+}
+
+fn parse_vec3(value: &str, name: &str) -> Result<Vec3, String> {
+    let parts = value
+        .split(',')
+        .map(|part| parse_number::<f32>(part.trim(), name))
+        .collect::<Result<Vec<f32>, String>>()?;
+    if parts.len() != 3 {
+        return Err(format!("{} expects three comma separated numbers", name));
+    }
+    Ok(Vec3::new(parts[0], parts[1], parts[2]))
+}
+
+fn parse_size(value: &str) -> Result<(u32, u32), String> {
+    let mut parts = value.split('x');
+    let width = parse_number::<u32>(parts.next().unwrap_or(""), "--size")?;
+    let height = parse_number::<u32>(parts.next().unwrap_or(""), "--size")?;
+    if parts.next().is_some() || width == 0 || height == 0 {
+        return Err(format!("invalid value '{}' for --size", value));
+    }
+    Ok((width, height))
+}
+
+impl Options {
+    // Arguments following the "render" subcommand.
+    pub fn parse(args: &[String]) -> Result<Options, String> {
+        let mut options = Options {
+            model: String::new(),
+            output: "out.png".to_string(),
+            width: 800,
+            height: 800,
+            eye: Vec3::new(0., 0., 5.),
+            target: Vec3::zero(),
+            up: Vec3::new(0., 1., 0.),
+            fov: 45.,
+            near: 0.1,
+            far: 1000.,
+            depth_range: DepthRange::Standard,
+            frames: 1,
+            shading: ShadingMode::Phong,
+            msaa: Multisample::Off,
+            alpha: false,
+            tone_map: None,
+            exposure: 0.,
+            color_space: ColorSpace::Srgb,
+            dither: false,
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if !arg.starts_with('-') {
+                if !options.model.is_empty() {
+                    return Err(format!("unexpected argument '{}'", arg));
+                }
+                options.model = arg.clone();
+                continue;
+            }
+
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for {}", arg))?;
+            match arg.as_str() {
+                "-o" | "--output" => options.output = value.clone(),
+                "-s" | "--size" => {
+                    let (width, height) = parse_size(value)?;
+                    options.width = width;
+                    options.height = height;
+                }
+                "--eye" => options.eye = parse_vec3(value, arg)?,
+                "--target" => options.target = parse_vec3(value, arg)?,
+                "--up" => options.up = parse_vec3(value, arg)?,
+                "--fov" => options.fov = parse_number(value, arg)?,
+                "--near" => options.near = parse_number(value, arg)?,
+                "--far" => options.far = parse_number(value, arg)?,
+                "--depth" => {
+                    options.depth_range = match value.as_str() {
+                        "standard" => DepthRange::Standard,
+                        "reversed" => DepthRange::Reversed,
+                        _ => return Err(format!("unknown depth mode '{}'", value)),
+                    }
+                }
+                "--frames" => options.frames = u32::max(parse_number(value, arg)?, 1),
+                "--shading" => {
+                    options.shading = match value.as_str() {
+                        "gouraud" => ShadingMode::Gouraud,
+                        "phong" => ShadingMode::Phong,
+                        _ => return Err(format!("unknown shading mode '{}'", value)),
+                    }
+                }
+                "--msaa" => {
+                    options.msaa = Multisample::from_count(parse_number(value, arg)?)
+                        .ok_or_else(|| format!("invalid value '{}' for {}", value, arg))?
+                }
+                "--channels" => {
+                    options.alpha = match value.as_str() {
+                        "rgb" => false,
+                        "rgba" => true,
+                        _ => return Err(format!("unknown channels '{}'", value)),
+                    }
+                }
+                "--tonemap" => {
+                    options.tone_map = match value.as_str() {
+                        "clamp" => Some(ToneMap::Clamp),
+                        "reinhard" => Some(ToneMap::Reinhard),
+                        "aces" => Some(ToneMap::Aces),
+                        _ => return Err(format!("unknown tone mapping '{}'", value)),
+                    }
+                }
+                "--exposure" => options.exposure = parse_number(value, arg)?,
+                "--color-space" => {
+                    options.color_space = match value.as_str() {
+                        "srgb" => ColorSpace::Srgb,
+                        "linear" => ColorSpace::Linear,
+                        _ => return Err(format!("unknown color space '{}'", value)),
+                    }
+                }
+                "--dither" => {
+                    options.dither = match value.as_str() {
+                        "on" => true,
+                        "off" => false,
+                        _ => return Err(format!("invalid value '{}' for {}", value, arg)),
+                    }
+                }
+                _ => return Err(format!("unknown option '{}'", arg)),
+            }
+        }
+
+        if options.model.is_empty() {
+            return Err("no model given".to_string());
+        }
+        // Also rejects NaN
+        if !(options.near > 0. && options.far > options.near) {
+            return Err(format!(
+                "--near has to be above 0 and below --far, got {} and {}",
+                options.near, options.far
+            ));
+        }
+        Ok(options)
+    }
+
+    // Output path of the given frame, "out.png" becomes "out_0001.png" and so on.
+    fn frame_path(&self, frame: u32) -> String {
+        if self.frames == 1 {
+            return self.output.clone();
+        }
+
+        let path = Path::new(&self.output);
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("frame");
+        let extension = path.extension().and_then(|s| s.to_str()).unwrap_or("png");
+        let name = format!("{}_{:04}.{}", stem, frame, extension);
+        match path.parent() {
+            Some(dir) => dir.join(name).to_string_lossy().into_owned(),
+            None => name,
+        }
+    }
+
+    fn hdr_output(&self) -> Option<String> {
+        let extension = Path::new(&self.output)
+            .extension()?
+            .to_str()?
+            .to_lowercase();
+        match extension.as_str() {
+            "exr" | "hdr" => Some(extension),
+            _ => None,
+        }
+    }
+
+    // The eye circles the target around the y axis, one full turn over all frames.
+    fn eye(&self, frame: u32) -> Vec3 {
+        let angle = 2. * std::f32::consts::PI * frame as f32 / self.frames as f32;
+        let (sin, cos) = angle.sin_cos();
+        let offset = self.eye - self.target;
+        self.target
+            + Vec3::new(
+                offset.x * cos + offset.z * sin,
+                offset.y,
+                -offset.x * sin + offset.z * cos,
+            )
+    }
+}
+
+// A mesh with its object transform and material, ready to be drawn every frame.
+struct Instance {
+    transform: Mat4,
+    mesh: Mesh,
+    material: Material,
+    // None for double-sided materials
+    cull_mode: CullMode,
+}
+
+impl Instance {
+    fn new(transform: Mat4, mesh: Mesh, material: Material) -> Instance {
+        Instance {
+            transform,
+            mesh,
+            material,
+            cull_mode: CullMode::Back,
+        }
+    }
+}
+
+struct Scene {
+    instances: Vec<Instance>,
+}
+
+fn load_scene(model: &str) -> Result<Scene, String> {
+    let default_material = Material::new(Vec3::splat(0.8));
+    let mut instances = Vec::new();
+
+    let mut builtin: Mesh = Mesh::construct();
+    match model {
+        "cube" => <Mesh as Cube>::new(&mut builtin),
+        "sphere" => <Mesh as Sphere>::new(&mut builtin, 32, 24),
+        "cone" => <Mesh as Cone>::new(&mut builtin, 32, 0.7, 1.),
+        "torus" => <Mesh as Torus>::new(&mut builtin, 32, 48, 0.5, 2.),
+        _ => {}
+    }
+    if !builtin.indices.is_empty() {
+        instances.push(Instance::new(Mat4::identity(), builtin, default_material));
+        return Ok(Scene { instances });
+    }
+
+    let extension = Path::new(model)
+        .extension()
+        .and_then(|s| s.to_str())
+        .map(|s| s.to_lowercase());
+    match extension.as_deref() {
+        Some("obj") => {
+            let obj = load_obj(model).map_err(|e| format!("{}: {}", model, e))?;
+            let materials = obj.materials;
+            for object in obj.objects {
+                let material = object
+                    .material
+                    .as_ref()
+                    .and_then(|name| materials.get(name))
+                    .map(|m| m.material())
+                    .unwrap_or(default_material);
+                instances.push(Instance::new(Mat4::identity(), object.mesh, material));
+            }
+        }
+        Some("gltf") | Some("glb") => {
+            let gltf = load_gltf(model).map_err(|e| format!("{}: {}", model, e))?;
+            for (transform, mesh) in gltf.instances() {
+                for primitive in mesh.primitives.iter() {
+                    let (material, double_sided) = match primitive.material {
+                        Some(i) => (gltf.materials[i].material(), gltf.materials[i].double_sided),
+                        None => (default_material, false),
+                    };
+                    let mut instance = Instance::new(transform, primitive.mesh.clone(), material);
+                    if double_sided {
+                        instance.cull_mode = CullMode::None;
+                    }
+                    instances.push(instance);
+                }
+            }
+        }
+        _ => return Err(format!("don't know how to load '{}'", model)),
+    }
+
+    Ok(Scene { instances })
+}
+
+// A white key light shining from above the camera and a dim fill from below, so the
+// visible side of the model stays lit while orbiting.
+fn camera_lighting(forward: Vec3, up: Vec3) -> Lighting {
+    let (forward, up) = (forward.normalize_or_zero(), up.normalize_or_zero());
+    let mut lighting = Lighting::new(Vec3::splat(0.1));
+    lighting.lights.push(Light::Directional {
+        direction: forward - up * 0.5,
+        color: Vec3::splat(0.9),
+    });
+    lighting.lights.push(Light::Directional {
+        direction: forward + up,
+        color: Vec3::new(0.2, 0.2, 0.25),
+    });
+    lighting
+}
+
+// Opaque instances go first. The blended ones follow farthest first, with their triangles
+// sorted as well and without writing depth so they don't hide each other.
+fn draw_scene(buf: &mut Buffer, scene: &mut Scene, lighting: &Lighting, shading: ShadingMode) {
+    let view = buf.world;
+    let mut blended = Vec::new();
+    for (i, instance) in scene.instances.iter().enumerate() {
+        if instance.material.alpha < 1. {
+            blended.push(i);
+            continue;
+        }
+        draw_instance(buf, instance, lighting, shading);
+    }
+
+    blend::sort_back_to_front(&mut blended, &view, |i| {
+        let instance = &scene.instances[*i];
+        let bounds = instance.mesh.bounds();
+        let center = bounds.map(|b| b.center()).unwrap_or(Vec3::zero());
+        (instance.transform * center.extend(1.)).xyz()
+    });
+
+    buf.blend = BlendMode::Alpha;
+    buf.depth_write = false;
+    for i in blended {
+        let instance = &mut scene.instances[i];
+        let obj2view = view * instance.transform;
+        instance.mesh.sort_back_to_front(&obj2view);
+        draw_instance(buf, instance, lighting, shading);
+    }
+    buf.blend = BlendMode::Replace;
+    buf.depth_write = true;
+}
+
+fn draw_instance(buf: &mut Buffer, instance: &Instance, lighting: &Lighting, shading: ShadingMode) {
+    buf.clear_object_matrices();
+    buf.transform(instance.transform);
+    buf.cull_mode = instance.cull_mode;
+    buf.draw_mesh_lit(&instance.mesh, lighting, &instance.material, shading);
+    buf.cull_mode = CullMode::Back;
+}
+
+pub fn render(options: &Options) -> Result<(), String> {
+    let mut scene = load_scene(&options.model)?;
+    let mut camera = Camera::new(
+        options.fov,
+        options.width as f32 / options.height as f32,
+        options.near,
+        options.far,
+    );
+    camera.depth_range = options.depth_range;
+
+    let mut buf = Buffer::new(
+        options.width,
+        options.height,
+        camera.projection(),
+        camera.view(),
+    );
+    buf.set_multisample(options.msaa);
+    buf.depth_compare = camera.depth_range.compare();
+    buf.set_hdr(options.tone_map.is_some() || options.hdr_output().is_some());
+    let mut tone_mapping = ToneMapping::new(options.tone_map.unwrap_or(ToneMap::Clamp));
+    tone_mapping.exposure = options.exposure;
+    buf.encoding = Encoding {
+        space: options.color_space,
+        dither: options.dither,
+    };
+
+    for frame in 0..options.frames {
+        camera.look_at(options.eye(frame), options.target, options.up);
+        let lighting = camera_lighting(camera.forward, camera.up);
+
+        buf.world = camera.view();
+        buf.clear_color(Color {
+            r: 0,
+            g: 0,
+            b: 0,
+            a: if options.alpha { 0 } else { 255 },
+        });
+        buf.clear_depth(camera.depth_range.clear_value());
+
+        draw_scene(&mut buf, &mut scene, &lighting, options.shading);
+
+        buf.resolve();
+        buf.tone_map(&tone_mapping);
+        let path = options.frame_path(frame);
+        let saved = match options.hdr_output().as_deref() {
+            Some("exr") => buf.save_to_exr(&path).map_err(|e| e.to_string()),
+            Some(_) => buf.save_to_hdr(&path).map_err(|e| e.to_string()),
+            None if options.alpha => buf.save_to_png_rgba(&path).map_err(|e| e.to_string()),
+            None => buf.save_to_png(&path).map_err(|e| e.to_string()),
+        };
+        saved.map_err(|e| format!("{}: {}", path, e))?;
+        println!("{}", path);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Options, String> {
+        let args: Vec<String> = args.split_whitespace().map(|a| a.to_string()).collect();
+        Options::parse(&args)
+    }
+
+    fn error(args: &str) -> String {
+        match parse(args) {
+            Err(e) => e,
+            Ok(_) => panic!("'{}' was accepted", args),
+        }
+    }
+
+    #[test]
+    fn defaults() {
+        let options = parse("cube").unwrap();
+        assert_eq!(options.model, "cube");
+        assert_eq!(options.output, "out.png");
+        assert_eq!((options.width, options.height), (800, 800));
+        assert_eq!((options.near, options.far), (0.1, 1000.));
+        assert_eq!(options.frames, 1);
+        assert_eq!(options.msaa, Multisample::Off);
+        assert_eq!(options.color_space, ColorSpace::Srgb);
+        assert!(options.tone_map.is_none());
+    }
+
+    #[test]
+    fn options() {
+        let options = parse(
+            "-o shots/a.exr model.obj -s 320x240 --eye 1,2,3 --near 0.5 --far 50 --depth reversed \
+             --frames 0 --shading gouraud --msaa 4 --channels rgba --tonemap aces \
+             --exposure -1.5 --color-space linear --dither on",
+        )
+        .unwrap();
+        assert_eq!(options.model, "model.obj");
+        assert_eq!(options.output, "shots/a.exr");
+        assert_eq!((options.width, options.height), (320, 240));
+        assert_eq!(options.eye, Vec3::new(1., 2., 3.));
+        assert_eq!((options.near, options.far), (0.5, 50.));
+        assert_eq!(options.depth_range, DepthRange::Reversed);
+        assert_eq!(options.frames, 1);
+        assert_eq!(options.shading, ShadingMode::Gouraud);
+        assert_eq!(options.msaa, Multisample::X4);
+        assert!(options.alpha);
+        assert_eq!(options.tone_map, Some(ToneMap::Aces));
+        assert_eq!(options.exposure, -1.5);
+        assert_eq!(options.color_space, ColorSpace::Linear);
+        assert!(options.dither);
+        assert_eq!(options.hdr_output().as_deref(), Some("exr"));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(error(""), "no model given");
+        assert_eq!(error("--fov 60"), "no model given");
+        assert_eq!(error("a.obj b.obj"), "unexpected argument 'b.obj'");
+        assert_eq!(error("cube --fov"), "missing value for --fov");
+        assert_eq!(error("cube --fov wide"), "invalid value 'wide' for --fov");
+        assert_eq!(error("cube --bogus 1"), "unknown option '--bogus'");
+        assert_eq!(error("cube -s 0x10"), "invalid value '0x10' for --size");
+        assert_eq!(
+            error("cube -s 10x10x10"),
+            "invalid value '10x10x10' for --size"
+        );
+        assert_eq!(
+            error("cube --eye 1,2"),
+            "--eye expects three comma separated numbers"
+        );
+        assert_eq!(error("cube --msaa 3"), "invalid value '3' for --msaa");
+        assert_eq!(error("cube --depth far"), "unknown depth mode 'far'");
+        assert_eq!(error("cube --shading flat"), "unknown shading mode 'flat'");
+        assert_eq!(error("cube --channels rg"), "unknown channels 'rg'");
+        assert_eq!(
+            error("cube --tonemap filmic"),
+            "unknown tone mapping 'filmic'"
+        );
+        assert_eq!(error("cube --color-space p3"), "unknown color space 'p3'");
+        assert_eq!(
+            error("cube --dither yes"),
+            "invalid value 'yes' for --dither"
+        );
+    }
+
+    #[test]
+    fn near_and_far() {
+        for args in [
+            "--near 0",
+            "--near -1",
+            "--near NaN",
+            "--far 0.05",
+            "--near 10 --far 10",
+            "--near 10 --far 5",
+        ] {
+            let e = error(&format!("cube {}", args));
+            assert!(e.starts_with("--near has to be above 0"), "{}", e);
+        }
+        assert!(parse("cube --near 0.01 --far 0.02").is_ok());
+    }
+
+    #[test]
+    fn frame_paths() {
+        let single = parse("cube -o dir/shot.png").unwrap();
+        assert_eq!(single.frame_path(0), "dir/shot.png");
+
+        let several = parse("cube -o dir/shot.png --frames 3").unwrap();
+        assert_eq!(several.frame_path(0), "dir/shot_0000.png");
+        assert_eq!(several.frame_path(12), "dir/shot_0012.png");
+
+        let bare = parse("cube -o shot --frames 2").unwrap();
+        assert_eq!(bare.frame_path(1), "shot_0001.png");
+        assert_eq!(bare.hdr_output(), None);
+    }
+}
@@ -0,0 +1,7 @@
+pub mod buffer;
+
+pub use crate::buffer::camera;
+pub use crate::buffer::color::Color;
+pub use crate::buffer::math;
+pub use crate::buffer::mesh;
+pub use crate::buffer::Buffer;
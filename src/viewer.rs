@@ -0,0 +1,111 @@
+use std::time::Instant;
+
+use minifb::{Key, MouseButton, MouseMode, Window, WindowOptions};
+
+use ruster::buffer::depth::DepthRange;
+use ruster::buffer::light::Light;
+use ruster::buffer::light::Lighting;
+use ruster::buffer::light::Material;
+use ruster::buffer::light::ShadingMode;
+use ruster::camera::Camera;
+use ruster::camera::CameraController;
+use ruster::camera::FirstPersonController;
+use ruster::camera::InputState;
+use ruster::camera::OrbitController;
+use ruster::camera::TrackballController;
+use ruster::math::vec3::Vec3;
+use ruster::mesh::*;
+use ruster::Buffer;
+use ruster::Color;
+
+// Consts
+const WIDTH: u32 = 800;
+const HEIGHT: u32 = 800;
+
+// 1, 2 and 3 switch between first-person, orbit and trackball controls. First-person looks
+// around with the mouse and moves with WASD, space and shift, the others rotate while the
+// left button is held and zoom with the wheel.
+pub fn run() {
+    let mut camera = Camera::new(45., WIDTH as f32 / HEIGHT as f32, 0.1, 1000.);
+    // Plenty of precision for the whole 0.1 to 1000 range
+    camera.depth_range = DepthRange::Reversed;
+    camera.look_at(Vec3::new(0., 0., 5.), Vec3::zero(), Vec3::new(0., 1., 0.));
+    let mut controller: Box<dyn CameraController> = Box::new(FirstPersonController::new(&camera));
+
+    let mut window = Window::new(
+        "Ruster",
+        WIDTH as usize,
+        HEIGHT as usize,
+        WindowOptions::default(),
+    )
+    .unwrap_or_else(|e| {
+        panic!("{}", e);
+    });
+
+    window.limit_update_rate(Some(std::time::Duration::from_micros(16600)));
+
+    let mut sphere: Mesh = Mesh::construct();
+    <Mesh as Sphere>::new(&mut sphere, 18, 13);
+    let material = Material::new(Vec3::new(0.8, 0.3, 0.2));
+    let mut lighting = Lighting::new(Vec3::splat(0.1));
+    lighting.lights.push(Light::Directional {
+        direction: Vec3::splat(-1.),
+        color: Vec3::splat(1.),
+    });
+
+    let mut buf: Buffer = Buffer::new(WIDTH, HEIGHT, camera.projection(), camera.view());
+    buf.depth_compare = camera.depth_range.compare();
+
+    let mut last_mouse: Option<(f32, f32)> = None;
+    let mut last_frame = Instant::now();
+
+    while window.is_open() && !window.is_key_down(Key::Escape) {
+        if window.is_key_down(Key::Key1) {
+            controller = Box::new(FirstPersonController::new(&camera));
+        } else if window.is_key_down(Key::Key2) {
+            controller = Box::new(OrbitController::new(&camera, Vec3::zero()));
+        } else if window.is_key_down(Key::Key3) {
+            controller = Box::new(TrackballController::new(Vec3::zero()));
+        }
+
+        let mouse = window.get_mouse_pos(MouseMode::Clamp);
+        let mouse_delta = match (mouse, last_mouse) {
+            (Some((x, y)), Some((last_x, last_y))) => [x - last_x, y - last_y],
+            _ => [0., 0.],
+        };
+        last_mouse = mouse;
+
+        let input = InputState {
+            forward: window.is_key_down(Key::W),
+            backward: window.is_key_down(Key::S),
+            left: window.is_key_down(Key::A),
+            right: window.is_key_down(Key::D),
+            up: window.is_key_down(Key::Space),
+            down: window.is_key_down(Key::LeftShift),
+            dragging: window.get_mouse_down(MouseButton::Left),
+            mouse_delta,
+            scroll: window.get_scroll_wheel().map(|(_, y)| y).unwrap_or(0.),
+        };
+
+        let now = Instant::now();
+        controller.update(&mut camera, &input, (now - last_frame).as_secs_f32());
+        last_frame = now;
+
+        buf.proj = camera.projection();
+        buf.world = camera.view();
+        buf.clear_color(Color {
+            r: 0,
+            g: 0,
+            b: 0,
+            a: 255,
+        });
+        buf.clear_depth(camera.depth_range.clear_value());
+
+        buf.draw_mesh_lit(&sphere, &lighting, &material, ShadingMode::Phong);
+        buf.resolve();
+
+        window
+            .update_with_buffer(buf.pixels(), WIDTH as usize, HEIGHT as usize)
+            .unwrap();
+    }
+}